@@ -1,9 +1,12 @@
+use crate::search::tt::MAX_SIZE_MB;
 use std::fmt::{self};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum OptionKind {
     Spin,
     String,
+    Check,
+    Button,
 }
 
 impl OptionKind {
@@ -11,6 +14,8 @@ impl OptionKind {
         match self {
             Self::Spin => "spin",
             Self::String => "string",
+            Self::Check => "check",
+            Self::Button => "button",
         }
     }
 }
@@ -21,6 +26,7 @@ impl fmt::Display for OptionKind {
     }
 }
 
+#[derive(Clone)]
 pub struct OptionDescriptor<T> {
     pub name: &'static str,
     pub kind: OptionKind,
@@ -133,10 +139,46 @@ impl fmt::Display for OptionDescriptor<f64> {
     }
 }
 
+impl fmt::Display for OptionDescriptor<bool> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "option name {} type {} default {}",
+            self.name, self.kind, self.value
+        )
+    }
+}
+
+impl fmt::Display for OptionDescriptor<String> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match self.value.is_empty() {
+            true => "<empty>",
+            false => &self.value,
+        };
+
+        write!(
+            f,
+            "option name {} type {} default {}",
+            self.name, self.kind, value
+        )
+    }
+}
+
+impl fmt::Display for OptionDescriptor<()> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "option name {} type {}", self.name, self.kind)
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub move_overhead: OptionDescriptor<usize>,
     pub threads: OptionDescriptor<u8>,
     pub hash: OptionDescriptor<usize>,
+    pub ponder: OptionDescriptor<bool>,
+    pub multi_pv: OptionDescriptor<usize>,
+    pub clear_hash: OptionDescriptor<()>,
+    pub hash_file: OptionDescriptor<String>,
     pub qsearch_depth: OptionDescriptor<u8>,
     pub rfp_depth: OptionDescriptor<u8>,
     pub rfp_base_margin: OptionDescriptor<i32>,
@@ -148,19 +190,40 @@ pub struct Config {
     pub nmp_margin: OptionDescriptor<u8>,
     pub nmp_divisor: OptionDescriptor<u8>,
     pub nmp_divisor_improving: OptionDescriptor<u8>,
+    pub nmp_verification_depth: OptionDescriptor<u8>,
     pub lmp_move_margin: OptionDescriptor<usize>,
     pub lmp_depth_factor: OptionDescriptor<u8>,
+    pub see_depth: OptionDescriptor<u8>,
+    pub see_capture_margin: OptionDescriptor<i32>,
+    pub see_quiet_margin: OptionDescriptor<i32>,
+    pub se_depth: OptionDescriptor<u8>,
+    pub se_tt_depth_margin: OptionDescriptor<u8>,
+    pub se_beta_margin: OptionDescriptor<i32>,
+    pub se_double_margin: OptionDescriptor<i32>,
+    pub se_double_limit: OptionDescriptor<u8>,
     pub lmr_depth: OptionDescriptor<u8>,
     pub lmr_move_margin: OptionDescriptor<usize>,
     pub lmr_quiet_margin: OptionDescriptor<f64>,
     pub lmr_quiet_divisor: OptionDescriptor<f64>,
     pub lmr_base_margin: OptionDescriptor<f64>,
     pub lmr_base_divisor: OptionDescriptor<f64>,
-    pub mo_tt_entry_value: OptionDescriptor<i32>,
+    pub lmr_history_divisor: OptionDescriptor<i32>,
     pub mo_capture_value: OptionDescriptor<i32>,
     pub mo_killer_value: OptionDescriptor<i32>,
+    pub asp_depth: OptionDescriptor<u8>,
+    pub asp_delta: OptionDescriptor<i32>,
+    pub asp_growth: OptionDescriptor<i32>,
     pub tc_time_divisor: OptionDescriptor<u64>,
-    pub tc_elapsed_factor: OptionDescriptor<i64>,
+    pub tc_moves_left_min: OptionDescriptor<u64>,
+    pub tc_moves_left_max: OptionDescriptor<u64>,
+    pub tc_inc_factor: OptionDescriptor<u64>,
+    pub tc_hard_factor: OptionDescriptor<u64>,
+    pub tc_stability_base: OptionDescriptor<u64>,
+    pub tc_stability_step: OptionDescriptor<u64>,
+    pub tc_score_drop_factor: OptionDescriptor<i64>,
+    pub tc_nodes_base: OptionDescriptor<u64>,
+    pub tc_nodes_factor: OptionDescriptor<u64>,
+    pub tc_elapsed_factor: OptionDescriptor<u64>,
 }
 
 impl Config {
@@ -178,14 +241,42 @@ impl Config {
                 kind: OptionKind::Spin,
                 value: 1,
                 min: 1,
-                max: 1,
+                max: 255,
             },
             hash: OptionDescriptor {
                 name: "Hash",
                 kind: OptionKind::Spin,
                 value: 255,
                 min: 1,
-                max: 1024,
+                max: MAX_SIZE_MB,
+            },
+            ponder: OptionDescriptor {
+                name: "Ponder",
+                kind: OptionKind::Check,
+                value: false,
+                min: false,
+                max: true,
+            },
+            multi_pv: OptionDescriptor {
+                name: "MultiPV",
+                kind: OptionKind::Spin,
+                value: 1,
+                min: 1,
+                max: 256,
+            },
+            clear_hash: OptionDescriptor {
+                name: "Clear Hash",
+                kind: OptionKind::Button,
+                value: (),
+                min: (),
+                max: (),
+            },
+            hash_file: OptionDescriptor {
+                name: "HashFile",
+                kind: OptionKind::String,
+                value: String::new(),
+                min: String::new(),
+                max: String::new(),
             },
             qsearch_depth: OptionDescriptor {
                 name: "QSearchDepth",
@@ -264,6 +355,13 @@ impl Config {
                 min: 1,
                 max: 20,
             },
+            nmp_verification_depth: OptionDescriptor {
+                name: "NMPVerificationDepth",
+                kind: OptionKind::Spin,
+                value: 12,
+                min: 1,
+                max: 64,
+            },
             lmp_move_margin: OptionDescriptor {
                 name: "LMPMoveMargin",
                 kind: OptionKind::Spin,
@@ -278,6 +376,62 @@ impl Config {
                 min: 1,
                 max: 20,
             },
+            see_depth: OptionDescriptor {
+                name: "SEEDepth",
+                kind: OptionKind::Spin,
+                value: 8,
+                min: 1,
+                max: 20,
+            },
+            see_capture_margin: OptionDescriptor {
+                name: "SEECaptureMargin",
+                kind: OptionKind::Spin,
+                value: 90,
+                min: 0,
+                max: 300,
+            },
+            see_quiet_margin: OptionDescriptor {
+                name: "SEEQuietMargin",
+                kind: OptionKind::Spin,
+                value: 50,
+                min: 0,
+                max: 300,
+            },
+            se_depth: OptionDescriptor {
+                name: "SEDepth",
+                kind: OptionKind::Spin,
+                value: 8,
+                min: 4,
+                max: 20,
+            },
+            se_tt_depth_margin: OptionDescriptor {
+                name: "SETTDepthMargin",
+                kind: OptionKind::Spin,
+                value: 3,
+                min: 1,
+                max: 8,
+            },
+            se_beta_margin: OptionDescriptor {
+                name: "SEBetaMargin",
+                kind: OptionKind::Spin,
+                value: 2,
+                min: 1,
+                max: 8,
+            },
+            se_double_margin: OptionDescriptor {
+                name: "SEDoubleMargin",
+                kind: OptionKind::Spin,
+                value: 20,
+                min: 0,
+                max: 100,
+            },
+            se_double_limit: OptionDescriptor {
+                name: "SEDoubleLimit",
+                kind: OptionKind::Spin,
+                value: 6,
+                min: 0,
+                max: 16,
+            },
             lmr_depth: OptionDescriptor {
                 name: "LMRDepth",
                 kind: OptionKind::Spin,
@@ -320,12 +474,12 @@ impl Config {
                 min: 1.0,
                 max: 10.0,
             },
-            mo_tt_entry_value: OptionDescriptor {
-                name: "MOTTEntryValue",
+            lmr_history_divisor: OptionDescriptor {
+                name: "LMRHistoryDivisor",
                 kind: OptionKind::Spin,
-                value: 229,
-                min: 1,
-                max: 500,
+                value: 8192,
+                min: 1024,
+                max: 32768,
             },
             mo_capture_value: OptionDescriptor {
                 name: "MOCaptureValue",
@@ -341,6 +495,27 @@ impl Config {
                 min: 0,
                 max: 500,
             },
+            asp_depth: OptionDescriptor {
+                name: "ASPDepth",
+                kind: OptionKind::Spin,
+                value: 4,
+                min: 1,
+                max: 20,
+            },
+            asp_delta: OptionDescriptor {
+                name: "ASPDelta",
+                kind: OptionKind::Spin,
+                value: 50,
+                min: 5,
+                max: 200,
+            },
+            asp_growth: OptionDescriptor {
+                name: "ASPGrowth",
+                kind: OptionKind::Spin,
+                value: 150,
+                min: 110,
+                max: 400,
+            },
             tc_time_divisor: OptionDescriptor {
                 name: "TCTimeDivisor",
                 kind: OptionKind::Spin,
@@ -348,10 +523,73 @@ impl Config {
                 min: 2,
                 max: 100,
             },
+            tc_moves_left_min: OptionDescriptor {
+                name: "TCMovesLeftMin",
+                kind: OptionKind::Spin,
+                value: 20,
+                min: 5,
+                max: 60,
+            },
+            tc_moves_left_max: OptionDescriptor {
+                name: "TCMovesLeftMax",
+                kind: OptionKind::Spin,
+                value: 40,
+                min: 10,
+                max: 100,
+            },
+            tc_inc_factor: OptionDescriptor {
+                name: "TCIncFactor",
+                kind: OptionKind::Spin,
+                value: 75,
+                min: 0,
+                max: 100,
+            },
+            tc_hard_factor: OptionDescriptor {
+                name: "TCHardFactor",
+                kind: OptionKind::Spin,
+                value: 300,
+                min: 100,
+                max: 1000,
+            },
+            tc_stability_base: OptionDescriptor {
+                name: "TCStabilityBase",
+                kind: OptionKind::Spin,
+                value: 140,
+                min: 50,
+                max: 300,
+            },
+            tc_stability_step: OptionDescriptor {
+                name: "TCStabilityStep",
+                kind: OptionKind::Spin,
+                value: 12,
+                min: 0,
+                max: 50,
+            },
+            tc_score_drop_factor: OptionDescriptor {
+                name: "TCScoreDropFactor",
+                kind: OptionKind::Spin,
+                value: 60,
+                min: 0,
+                max: 200,
+            },
+            tc_nodes_base: OptionDescriptor {
+                name: "TCNodesBase",
+                kind: OptionKind::Spin,
+                value: 160,
+                min: 50,
+                max: 300,
+            },
+            tc_nodes_factor: OptionDescriptor {
+                name: "TCNodesFactor",
+                kind: OptionKind::Spin,
+                value: 100,
+                min: 0,
+                max: 200,
+            },
             tc_elapsed_factor: OptionDescriptor {
                 name: "TCElapsedFactor",
                 kind: OptionKind::Spin,
-                value: 8,
+                value: 2,
                 min: 1,
                 max: 10,
             },
@@ -15,6 +15,7 @@ mod eval; // Position evaluation
 mod logger;
 mod moves; // Move generation and handling
 mod nnue; // Neural Network evaluation
+mod score; // Score constants and mate scores
 mod search; // Search algorithm implementation
 mod time_control; // Time management
 mod uci; // Universal Chess Interface protocol
@@ -12,6 +12,7 @@ mod eval; // Position evaluation
 mod logger;
 mod moves; // Move generation and handling
 mod nnue; // Neural Network evaluation
+mod score; // Score constants and mate scores
 mod search; // Search algorithm implementation
 mod time_control; // Time management
 mod uci; // Universal Chess Interface protocol
@@ -48,12 +49,13 @@ pub fn main() {
 
         io::stdin().read_line(&mut input).ok().unwrap();
         let command = input.trim().to_string();
+        let quit = command == "quit";
 
-        if command == "quit" {
+        tx.send(command).unwrap();
+
+        if quit {
             break;
         }
-
-        tx.send(command).unwrap();
     }
 
     drop(tx);
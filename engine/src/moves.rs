@@ -1,5 +1,6 @@
 /// Module providing chess move-related constants and utilities.
-use shakmaty::{Move, Role, Square};
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, Move, Role, Square};
 
 /// Default chess move constant for initialization purposes.
 /// Represents a null move from A1 to A1 with a pawn, no promotion, and no capture.
@@ -12,3 +13,36 @@ pub(crate) static DEFAULT_MOVE: Move = Move::Normal {
     capture: None,
 };
 
+/// Packs a move into 16 bits: origin square, target square and promotion role.
+/// Castling moves are packed as king to rook, like shakmaty represents them.
+/// A packed value of 0 (a1a1) never matches a legal move.
+pub fn pack_move(m: &Move) -> u16 {
+    let from = m.from().map_or(0, |sq| sq as u16);
+    let to = m.to() as u16;
+    let promotion = m.promotion().map_or(0, |role| role as u16);
+
+    from | to << 6 | promotion << 12
+}
+
+/// Decodes a move packed with `pack_move` in the given position.
+///
+/// # Returns
+/// * The move if it is legal in the position, `None` otherwise, which also
+///   rejects moves read from a colliding transposition table entry
+pub fn unpack_move(packed: u16, pos: &Chess) -> Option<Move> {
+    if packed == 0 {
+        return None;
+    }
+
+    let from = Square::new(packed as u32 & 63);
+    let to = Square::new((packed >> 6) as u32 & 63);
+    let promotion = Role::try_from(packed >> 12).ok();
+
+    UciMove::Normal {
+        from,
+        to,
+        promotion,
+    }
+    .to_move(pos)
+    .ok()
+}
@@ -0,0 +1,107 @@
+//! Score constants and conversions of mate scores.
+//! A mate is scored as `MATE - ply`, where `ply` is the distance from the
+//! root to the checkmate, so that shorter mates are preferred.
+
+/// Score of a checkmate delivered at the root, mate scores must fit the
+/// 16 bits of a transposition table entry
+pub const MATE: i32 = 32000;
+/// Bound of the search window, above any reachable score
+pub const INFINITY: i32 = MATE + 1;
+/// Maximum search ply, mirrors the size of the NNUE accumulator stack
+pub const MAX_PLY: i32 = 128;
+/// Scores beyond this value in absolute terms are mate scores
+pub const MATE_BOUND: i32 = MATE - MAX_PLY;
+
+/// Checks if a score encodes a forced mate, for either side.
+pub fn is_mate(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// Converts a root-relative mate score to a distance from the current node,
+/// as the same position can be reached at different plies.
+///
+/// # Arguments
+/// * `score` - Score relative to the root
+/// * `ply` - Ply of the node being stored
+pub fn to_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        score if score >= MATE_BOUND => score + ply as i32,
+        score if score <= -MATE_BOUND => score - ply as i32,
+        score => score,
+    }
+}
+
+/// Converts a mate score read from the transposition table back to a
+/// root-relative score, the inverse of `to_tt`.
+///
+/// # Arguments
+/// * `score` - Score relative to the stored node
+/// * `ply` - Ply of the node probing the table
+pub fn from_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        score if score >= MATE_BOUND => score - ply as i32,
+        score if score <= -MATE_BOUND => score + ply as i32,
+        score => score,
+    }
+}
+
+/// Returns the number of moves until mate, negative when the side to move
+/// gets mated, or `None` if the score is not a mate score.
+pub fn mate_in(score: i32) -> Option<i32> {
+    match score {
+        score if score >= MATE_BOUND => Some((MATE - score + 1) / 2),
+        score if score <= -MATE_BOUND => Some(-(MATE + score) / 2),
+        _ => None,
+    }
+}
+
+/// Formats a score for the UCI `info` line, `mate N` or `cp N`.
+pub fn to_uci(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tt_conversion_keeps_normal_scores() {
+        for score in [-MATE_BOUND + 1, -250, 0, 250, MATE_BOUND - 1] {
+            assert_eq!(to_tt(score, 7), score);
+            assert_eq!(from_tt(score, 7), score);
+        }
+    }
+
+    #[test]
+    fn tt_conversion_moves_mate_scores_to_the_probing_ply() {
+        /* Mate found 10 plies from the root, stored at ply 3 and probed at ply 5 */
+        assert_eq!(to_tt(MATE - 10, 3), MATE - 7);
+        assert_eq!(from_tt(MATE - 7, 5), MATE - 12);
+
+        assert_eq!(to_tt(-MATE + 10, 3), -MATE + 7);
+        assert_eq!(from_tt(-MATE + 7, 5), -MATE + 12);
+    }
+
+    #[test]
+    fn tt_conversion_round_trips() {
+        for score in [MATE - 1, MATE - 40, -MATE + 2, -MATE + 40, 123] {
+            for ply in [0, 1, 17, 60] {
+                assert_eq!(from_tt(to_tt(score, ply), ply), score);
+            }
+        }
+    }
+
+    #[test]
+    fn mate_in_counts_moves() {
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-MATE + 2), Some(-1));
+        assert_eq!(mate_in(-MATE + 4), Some(-2));
+        assert_eq!(mate_in(100), None);
+        assert_eq!(to_uci(MATE - 5), "mate 3");
+        assert_eq!(to_uci(-35), "cp -35");
+    }
+}
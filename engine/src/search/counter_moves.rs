@@ -0,0 +1,23 @@
+use super::history::piece_index;
+use shakmaty::{Move, Piece, Square};
+
+/// Quiet moves which refuted a previous move, indexed by its piece and target square.
+pub struct CounterMoves {
+    table: [[Option<Move>; 64]; 12],
+}
+
+impl CounterMoves {
+    pub fn new() -> Self {
+        Self {
+            table: [const { [const { None }; 64] }; 12],
+        }
+    }
+
+    pub fn get(&self, prev: (Piece, Square)) -> Option<&Move> {
+        self.table[piece_index(prev.0)][prev.1 as usize].as_ref()
+    }
+
+    pub fn store(&mut self, prev: (Piece, Square), m: &Move) {
+        self.table[piece_index(prev.0)][prev.1 as usize] = Some(m.clone());
+    }
+}
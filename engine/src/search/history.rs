@@ -1,29 +1,83 @@
-use shakmaty::{Role, Square};
+use shakmaty::{Color, Piece, Role, Square};
+
+/// Bound of the history scores, updates shrink as a score gets closer to it.
+pub const MAX_HISTORY: i32 = 16384;
+
+/// Returns the history bonus of a quiet move causing a cut-off at the given depth.
+pub fn bonus(depth: u8) -> i32 {
+    (16 * depth as i32 * depth as i32).min(MAX_HISTORY / 8)
+}
+
+/// Applies a bonus or a malus to a history score, keeping it within `MAX_HISTORY`.
+fn apply(entry: &mut i32, value: i32) {
+    let value = value.clamp(-MAX_HISTORY, MAX_HISTORY);
+    *entry += value - *entry * value.abs() / MAX_HISTORY;
+}
+
+/// Index of a piece among the 12 pieces of both colors.
+pub fn piece_index(piece: Piece) -> usize {
+    piece.color as usize * 6 + piece.role as usize - 1
+}
 
 pub struct HistoryTable {
-    table: [[i32; 64]; 6],
+    table: [[[i32; 64]; 6]; 2],
 }
 
 impl HistoryTable {
     pub fn new() -> Self {
         Self {
-            table: [[0; 64]; 6],
+            table: [[[0; 64]; 6]; 2],
         }
     }
 
-    pub fn update(&mut self, piece: Role, to: Square, value: i32) {
-        self.table[piece as usize - 1][to as usize] += value;
+    pub fn update(&mut self, color: Color, piece: Role, to: Square, value: i32) {
+        apply(
+            &mut self.table[color as usize][piece as usize - 1][to as usize],
+            value,
+        );
     }
 
-    pub fn get(&self, piece: Role, to: Square) -> i32 {
-        self.table[piece as usize - 1][to as usize]
+    pub fn get(&self, color: Color, piece: Role, to: Square) -> i32 {
+        self.table[color as usize][piece as usize - 1][to as usize]
     }
 
     pub fn new_search(&mut self) {
-        for p in self.table.iter_mut() {
+        for p in self.table.iter_mut().flatten() {
             for val in p.iter_mut() {
                 *val /= 2;
             }
         }
     }
 }
+
+/// History of quiet moves following a previous move, indexed by the piece and
+/// target square of both moves.
+pub struct ContinuationHistory {
+    table: Vec<i32>,
+}
+
+impl ContinuationHistory {
+    pub fn new() -> Self {
+        Self {
+            table: vec![0; 12 * 64 * 12 * 64],
+        }
+    }
+
+    fn index(prev: (Piece, Square), piece: Piece, to: Square) -> usize {
+        ((piece_index(prev.0) * 64 + prev.1 as usize) * 12 + piece_index(piece)) * 64 + to as usize
+    }
+
+    pub fn update(&mut self, prev: (Piece, Square), piece: Piece, to: Square, value: i32) {
+        apply(&mut self.table[Self::index(prev, piece, to)], value);
+    }
+
+    pub fn get(&self, prev: (Piece, Square), piece: Piece, to: Square) -> i32 {
+        self.table[Self::index(prev, piece, to)]
+    }
+
+    pub fn new_search(&mut self) {
+        for val in self.table.iter_mut() {
+            *val /= 2;
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use shakmaty::zobrist::Zobrist64;
 
+#[derive(Clone)]
 pub struct HistoryStack {
     pub stack: Vec<HistoryStackEntry>,
 }
 
+#[derive(Clone)]
 pub struct HistoryStackEntry {
     key: Zobrist64,
-    eval: Option<i32>,
+    /// Position reached by a null move, no earlier position can repeat after it
+    null: bool,
 }
 
 impl HistoryStack {
@@ -16,16 +19,30 @@ impl HistoryStack {
 }
 
 impl HistoryStack {
-    pub fn push(&mut self, zobrist: Zobrist64, eval: Option<i32>) {
-        self.stack.push(HistoryStackEntry { key: zobrist, eval });
+    pub fn push(&mut self, zobrist: Zobrist64) {
+        self.stack.push(HistoryStackEntry {
+            key: zobrist,
+            null: false,
+        });
+    }
+
+    /// Pushes the position reached by a null move, which starts a new repetition window.
+    pub fn push_null(&mut self, zobrist: Zobrist64) {
+        self.stack.push(HistoryStackEntry {
+            key: zobrist,
+            null: true,
+        });
     }
 
     pub fn pop(&mut self) -> Option<HistoryStackEntry> {
         self.stack.pop()
     }
 
+    /// Counts the previous occurrences of a position since the last null move.
     pub fn count_zobrist(&self, zobrist: Zobrist64) -> usize {
-        self.stack
+        let start = self.stack.iter().rposition(|h| h.null).unwrap_or(0);
+
+        self.stack[start..]
             .iter()
             .rev()
             .skip(1)
@@ -33,14 +50,6 @@ impl HistoryStack {
             .count()
     }
 
-    pub fn get_eval(&self, ply: usize) -> Option<i32> {
-        if ply >= self.stack.len() {
-            return None;
-        }
-
-        self.stack[ply].eval
-    }
-
     pub fn clear(&mut self) {
         self.stack.clear();
     }
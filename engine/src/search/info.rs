@@ -1,10 +1,24 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
 /// Contains information about the current search process including
 /// statistical data and search progress metrics.
 pub struct SearchInfo {
     /// Total number of nodes visited during the search
-    pub nodes: u32,
+    pub nodes: u64,
+    /// Node count published periodically so that the main thread can
+    /// report the nodes searched by every thread
+    pub shared_nodes: Arc<AtomicU64>,
     /// Current depth of the search in plies
     pub depth: u8,
+    /// Maximum ply reached in the current iteration, including quiescence search
+    pub sel_depth: usize,
+    /// Nodes spent on the subtree of the current best root move
+    pub best_move_nodes: u64,
+    /// Null moves are not tried below this ply while a null move cut-off is verified
+    pub nmp_min_ply: usize,
+    /// Set once the search has been interrupted, the scores returned since then are meaningless
+    pub aborted: bool,
 }
 
 /// Implements default initialization for SearchInfo struct
@@ -12,7 +26,12 @@ impl Default for SearchInfo {
     fn default() -> Self {
         SearchInfo {
             nodes: 0,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
             depth: 0,
+            sel_depth: 0,
+            best_move_nodes: 0,
+            nmp_min_ply: 0,
+            aborted: false,
         }
     }
-}
\ No newline at end of file
+}
@@ -1,19 +1,25 @@
+pub mod counter_moves;
 pub mod history;
 pub mod history_stack;
 pub mod info;
-pub mod killers;
 pub mod move_picker;
 pub mod params;
+pub mod position;
 pub mod pv;
 pub mod search;
+pub mod see;
+pub mod stack;
 pub mod tt;
 
-use history::HistoryTable;
+use counter_moves::CounterMoves;
+use history::{ContinuationHistory, HistoryTable};
 use info::SearchInfo;
-use killers::Killers;
 use params::SearchParams;
 use pv::PvTable;
-use shakmaty::{Chess, Position};
+use shakmaty::{Chess, Move, Piece, Position, Square};
+use stack::SearchStack;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use tt::TranspositionTable;
 
 use crate::config::Config;
@@ -26,30 +32,102 @@ pub struct SearchState {
     pub info: SearchInfo,
     pub tc: TimeController,
     pub nnue: NNUEState,
-    pub tt: TranspositionTable,
+    /// Shared with the helper threads
+    pub tt: Arc<TranspositionTable>,
     pub hstack: HistoryStack,
     pub pv: PvTable,
-    pub km: Killers,
+    pub stack: SearchStack,
     pub cfg: Config,
     pub hist: HistoryTable,
+    /// Continuation histories of the moves played one and two plies before
+    pub cont_hist: [ContinuationHistory; 2],
+    pub counter_moves: CounterMoves,
+    /// Root moves skipped by the current search, the best moves of the previous MultiPV lines
+    pub root_excluded: Vec<Move>,
+    /// Set from the UCI thread to interrupt the search, shared with the helper threads
+    pub stop: Arc<AtomicBool>,
+    /// Cleared from the UCI thread on `ponderhit`
+    pub ponder: Arc<AtomicBool>,
 }
 
 impl SearchState {
     pub fn new() -> Self {
         let cfg = Config::default();
+        let tt = TranspositionTable::new(cfg.hash.value, cfg.threads.value as usize);
 
+        Self::with_shared(Arc::new(tt), Arc::new(AtomicBool::new(false)), cfg)
+    }
+
+    /// Creates a state using an existing transposition table and stop flag,
+    /// used to build the helper threads of a search.
+    pub fn with_shared(tt: Arc<TranspositionTable>, stop: Arc<AtomicBool>, cfg: Config) -> Self {
         Self {
             game: Chess::default(),
-            tt: TranspositionTable::new(cfg.hash.value * 1024 * 1024 / 24),
+            tt,
             info: SearchInfo::default(),
             tc: TimeController::default(),
             params: SearchParams::default(),
             nnue: NNUEState::from_board(Chess::default().board()),
             hstack: HistoryStack::new(),
             pv: PvTable::default(),
-            km: Killers::new(),
+            stack: SearchStack::new(),
             hist: HistoryTable::new(),
+            cont_hist: [ContinuationHistory::new(), ContinuationHistory::new()],
+            counter_moves: CounterMoves::new(),
+            root_excluded: Vec::new(),
+            stop,
+            ponder: Arc::new(AtomicBool::new(false)),
             cfg,
         }
     }
+
+    /// Resets the per-game state: position, heuristics and stacks.
+    /// The transposition table is shared and cleared separately.
+    pub fn new_game(&mut self) {
+        self.game = Chess::default();
+        self.nnue = NNUEState::from_board(self.game.board());
+        self.hstack = HistoryStack::new();
+        self.pv = PvTable::default();
+        self.stack.clear();
+        self.hist = HistoryTable::new();
+        self.cont_hist = [ContinuationHistory::new(), ContinuationHistory::new()];
+        self.counter_moves = CounterMoves::new();
+        self.root_excluded.clear();
+    }
+
+    /// Returns the history score of a quiet move, combining the history table
+    /// with the continuation histories of the previous moves.
+    ///
+    /// # Arguments
+    /// * `ply` - Ply the move is played from
+    /// * `piece` - Moved piece
+    /// * `to` - Target square of the move
+    pub fn quiet_history(&self, ply: usize, piece: Piece, to: Square) -> i32 {
+        let mut score = self.hist.get(piece.color, piece.role, to);
+
+        for (i, table) in self.cont_hist.iter().enumerate() {
+            if let Some(prev) = ply.checked_sub(i + 1).and_then(|p| self.stack[p].cont_hist) {
+                score += table.get(prev, piece, to);
+            }
+        }
+
+        score
+    }
+
+    /// Rewards or penalizes a quiet move in the history table and the continuation histories.
+    ///
+    /// # Arguments
+    /// * `ply` - Ply the move was played from
+    /// * `piece` - Moved piece
+    /// * `to` - Target square of the move
+    /// * `value` - Bonus, or malus when negative
+    pub fn update_quiet_history(&mut self, ply: usize, piece: Piece, to: Square, value: i32) {
+        self.hist.update(piece.color, piece.role, to, value);
+
+        for (i, table) in self.cont_hist.iter_mut().enumerate() {
+            if let Some(prev) = ply.checked_sub(i + 1).and_then(|p| self.stack[p].cont_hist) {
+                table.update(prev, piece, to, value);
+            }
+        }
+    }
 }
@@ -1,72 +1,259 @@
-use super::{tt::TranspositionTableEntry, SearchState};
-use shakmaty::{Move, MoveList, Role};
+use super::stack::KILLERS;
+use super::{see, SearchState};
+use shakmaty::{Chess, Move, MoveList, Piece, Position};
 
-const MO_FACTOR: i32 = 10000;
-
-pub struct MovePicker<'a> {
-    order: Vec<&'a Move>,
-    curr: usize,
+/// Stages of the move picker, in the order the moves are tried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    LowCaptures,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
 }
 
-impl<'a> Iterator for MovePicker<'a> {
-    type Item = &'a Move;
+/// Generates and orders the moves of a node lazily, one stage at a time,
+/// so that a cut-off on an early move saves the generation of the others.
+pub struct MovePicker {
+    next_stage: Stage,
+    /// Stage the last returned move was picked from
+    picked: Stage,
+    ply: usize,
+    tt_move: Option<Move>,
+    counter_move: Option<Move>,
+    /// Scored moves of the current generated stage
+    moves: Vec<(Move, i32)>,
+    /// Captures losing material, tried last
+    bad_captures: Vec<Move>,
+    /// Killers and counter move already returned, excluded from the quiets
+    refutations: Vec<Move>,
+    index: usize,
+    skip_quiets: bool,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.curr >= self.order.len() {
-            return None;
+impl MovePicker {
+    pub fn new(tt_move: Option<Move>, counter_move: Option<Move>, ply: usize) -> Self {
+        Self {
+            next_stage: Stage::TTMove,
+            picked: Stage::TTMove,
+            ply,
+            tt_move,
+            counter_move,
+            moves: Vec::new(),
+            bad_captures: Vec::new(),
+            refutations: Vec::new(),
+            index: 0,
+            skip_quiets: false,
         }
+    }
 
-        let move_ref = self.order[self.curr];
-        self.curr += 1;
+    /// Returns the stage the last returned move was picked from.
+    pub fn stage(&self) -> Stage {
+        self.picked
+    }
 
-        Some(move_ref)
+    /// Stops returning quiet moves, the captures and promotions are still returned.
+    pub fn skip_quiets(&mut self) {
+        self.skip_quiets = true;
     }
-}
 
-impl<'a> MovePicker<'a> {
-    pub fn new(
-        moves: &'a MoveList,
-        state: &SearchState,
-        entry: &TranspositionTableEntry,
-        ply: usize,
-    ) -> Self {
-        let mut scored_moves: Vec<(&'a Move, i32)> = moves
-            .iter()
-            .map(|m_ref| (m_ref, Self::move_importance(state, entry, ply, m_ref)))
-            .collect();
+    /// Returns the next move to search, or `None` once every move has been picked.
+    ///
+    /// # Arguments
+    /// * `pos` - Position the moves are generated for
+    /// * `state` - Search state holding the ordering heuristics
+    pub fn next(&mut self, pos: &Chess, state: &SearchState) -> Option<Move> {
+        loop {
+            let m = self.next_candidate(pos, state)?;
 
-        scored_moves.sort_by_key(|&(_, score)| -score);
+            if self.ply > 0 || Self::is_searched_root_move(state, &m) {
+                return Some(m);
+            }
+        }
+    }
 
-        let order: Vec<&'a Move> = scored_moves.into_iter().map(|(m_ref, _)| m_ref).collect();
+    fn next_candidate(&mut self, pos: &Chess, state: &SearchState) -> Option<Move> {
+        loop {
+            self.picked = self.next_stage;
 
-        Self { order, curr: 0 }
-    }
+            match self.next_stage {
+                Stage::TTMove => {
+                    self.next_stage = Stage::GenerateCaptures;
 
-    fn move_importance(
-        state: &SearchState,
-        entry: &TranspositionTableEntry,
-        ply: usize,
-        m: &Move,
-    ) -> i32 {
-        if *m == entry._move {
-            return state.cfg.mo_tt_entry_value.value * MO_FACTOR;
-        }
+                    if let Some(m) = &self.tt_move {
+                        return Some(m.clone());
+                    }
+                }
 
-        if m.is_capture() {
-            let moving_piece_value = m.role() as i32;
-            let captured_piece_value = m.capture().unwrap_or(Role::Pawn) as i32;
-            return (state.cfg.mo_capture_value.value * captured_piece_value - moving_piece_value)
-                * MO_FACTOR;
-        }
+                Stage::GenerateCaptures => {
+                    let mut moves = pos.capture_moves();
+                    moves.extend(
+                        pos.promotion_moves()
+                            .into_iter()
+                            .filter(|m| !m.is_capture()),
+                    );
 
-        if state.km.get(ply).contains(m) {
-            return state.cfg.mo_killer_value.value * MO_FACTOR;
-        }
+                    self.moves = self.score(moves, |m| Self::capture_importance(state, m));
+                    self.next_stage = Stage::GoodCaptures;
+                }
+
+                Stage::GoodCaptures => {
+                    /* Captures worth less than a killer are left for after the killers */
+                    while let Some(m) = self.pick_best(state.cfg.mo_killer_value.value) {
+                        match see::see(pos, &m, 0) {
+                            true => return Some(m),
+                            false => self.bad_captures.push(m),
+                        }
+                    }
+
+                    self.next_stage = Stage::Killers;
+                }
+
+                Stage::Killers => {
+                    let killers = &state.stack[self.ply].killers;
+
+                    while !self.skip_quiets && self.index < KILLERS {
+                        let killer = &killers[self.index];
+                        self.index += 1;
+
+                        if let Some(m) = killer {
+                            if self.is_refutation(pos, m) {
+                                self.refutations.push(m.clone());
+                                return Some(m.clone());
+                            }
+                        }
+                    }
+
+                    self.index = 0;
+                    self.next_stage = Stage::CounterMove;
+                }
+
+                Stage::CounterMove => {
+                    self.next_stage = Stage::LowCaptures;
+
+                    if self.skip_quiets {
+                        continue;
+                    }
+
+                    if let Some(m) = self.counter_move.take() {
+                        if self.is_refutation(pos, &m) {
+                            self.refutations.push(m.clone());
+                            return Some(m);
+                        }
+                    }
+                }
+
+                Stage::LowCaptures => {
+                    while let Some(m) = self.pick_best(i32::MIN) {
+                        match see::see(pos, &m, 0) {
+                            true => return Some(m),
+                            false => self.bad_captures.push(m),
+                        }
+                    }
 
-        if m.is_promotion() {
-            return m.promotion().unwrap() as i32;
+                    self.next_stage = Stage::GenerateQuiets;
+                }
+
+                Stage::GenerateQuiets => {
+                    if self.skip_quiets {
+                        self.next_stage = Stage::BadCaptures;
+                        continue;
+                    }
+
+                    let mut moves = pos.legal_moves();
+                    moves.retain(|m| {
+                        !m.is_capture() && !m.is_promotion() && !self.refutations.contains(m)
+                    });
+
+                    self.moves = self.score(moves, |m| {
+                        let piece = Piece {
+                            color: pos.turn(),
+                            role: m.role(),
+                        };
+
+                        state.quiet_history(self.ply, piece, m.to())
+                    });
+                    self.next_stage = Stage::Quiets;
+                }
+
+                Stage::Quiets => {
+                    if !self.skip_quiets {
+                        if let Some(m) = self.pick_best(i32::MIN) {
+                            return Some(m);
+                        }
+                    }
+
+                    self.next_stage = Stage::BadCaptures;
+                }
+
+                Stage::BadCaptures => {
+                    if self.index < self.bad_captures.len() {
+                        self.index += 1;
+                        return Some(self.bad_captures[self.index - 1].clone());
+                    }
+
+                    self.next_stage = Stage::Done;
+                }
+
+                Stage::Done => return None,
+            }
         }
+    }
+
+    /// Scores the generated moves of a stage, leaving out the TT move already returned.
+    fn score(&self, moves: MoveList, importance: impl Fn(&Move) -> i32) -> Vec<(Move, i32)> {
+        moves
+            .into_iter()
+            .filter(|m| self.tt_move.as_ref() != Some(m))
+            .map(|m| {
+                let score = importance(&m);
+                (m, score)
+            })
+            .collect()
+    }
+
+    /// Removes and returns the highest scored move of the current stage, if
+    /// its score reaches the given minimum.
+    fn pick_best(&mut self, min_score: i32) -> Option<Move> {
+        let (best, _) = self
+            .moves
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, score))| *score)
+            .filter(|(_, (_, score))| *score >= min_score)?;
+
+        Some(self.moves.swap_remove(best).0)
+    }
+
+    /// Checks whether a killer or counter move can be played as a quiet move in this
+    /// position and has not been returned yet.
+    fn is_refutation(&self, pos: &Chess, m: &Move) -> bool {
+        !m.is_capture()
+            && !m.is_promotion()
+            && self.tt_move.as_ref() != Some(m)
+            && !self.refutations.contains(m)
+            && pos.is_legal(m)
+    }
+
+    /// Checks whether a root move is part of the search, honouring `searchmoves`
+    /// and skipping the moves of the previous MultiPV lines.
+    fn is_searched_root_move(state: &SearchState, m: &Move) -> bool {
+        (state.params.search_moves.is_empty() || state.params.search_moves.contains(m))
+            && !state.root_excluded.contains(m)
+    }
+
+    /// Orders the captures and promotions by Most Valuable Victim - Least Valuable Attacker.
+    fn capture_importance(state: &SearchState, m: &Move) -> i32 {
+        let moving_piece_value = m.role() as i32;
+        let captured_piece_value = m.capture().map_or(0, |role| role as i32);
+        let promotion_value = m.promotion().map_or(0, |role| role as i32);
 
-        state.hist.get(m.role(), m.to())
+        state.cfg.mo_capture_value.value * captured_piece_value + promotion_value
+            - moving_piece_value
     }
 }
@@ -1,3 +1,5 @@
+use shakmaty::Move;
+
 /// Parameters controlling the chess engine's search behavior,
 /// including depth limits and time controls.
 pub struct SearchParams {
@@ -9,6 +11,22 @@ pub struct SearchParams {
     pub w_time: u128,
     /// Remaining time for Black in milliseconds
     pub b_time: u128,
+    /// Increment per move for White in milliseconds
+    pub w_inc: u128,
+    /// Increment per move for Black in milliseconds
+    pub b_inc: u128,
+    /// Search started with `go ponder`, the clock waits for `ponderhit`
+    pub ponder: bool,
+    /// Search started with `go infinite`, runs until `stop`
+    pub infinite: bool,
+    /// Maximum number of nodes to search
+    pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves is found
+    pub mate: Option<u8>,
+    /// Number of moves until the next time control
+    pub moves_to_go: Option<u32>,
+    /// Root moves the search is restricted to, all of them when empty
+    pub search_moves: Vec<Move>,
 }
 
 /// Implements default initialization for SearchParams struct
@@ -19,6 +37,14 @@ impl Default for SearchParams {
             move_time: 0,
             w_time: 0,
             b_time: 0,
+            w_inc: 0,
+            b_inc: 0,
+            ponder: false,
+            infinite: false,
+            nodes: None,
+            mate: None,
+            moves_to_go: None,
+            search_moves: Vec::new(),
         }
     }
 }
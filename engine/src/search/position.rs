@@ -0,0 +1,259 @@
+//! Position updated in place by the search.
+//! Moves are played on a single position and taken back from the undo
+//! information kept on the search stack, the Zobrist key is updated
+//! incrementally instead of being recomputed at every node.
+
+use shakmaty::zobrist::{Zobrist64, ZobristHash, ZobristValue};
+use shakmaty::{Castles, CastlingSide, Chess, Color, EnPassantMode, Move, Position, Square};
+use std::ops::Deref;
+
+/// State needed to take a move back.
+#[derive(Clone, Default)]
+pub struct Undo {
+    pos: Chess,
+    key: Zobrist64,
+}
+
+pub struct SearchPosition {
+    pos: Chess,
+    key: Zobrist64,
+}
+
+impl SearchPosition {
+    pub fn new(pos: Chess) -> Self {
+        let key = pos.zobrist_hash(EnPassantMode::Legal);
+
+        Self { pos, key }
+    }
+
+    /// Zobrist key of the position, equal to `zobrist_hash(EnPassantMode::Legal)`.
+    pub fn key(&self) -> Zobrist64 {
+        self.key
+    }
+
+    /// Plays a legal move, saving what is needed to take it back.
+    ///
+    /// # Arguments
+    /// * `m` - Legal move to play
+    /// * `undo` - Undo information of the ply, overwritten
+    pub fn play(&mut self, m: &Move, undo: &mut Undo) {
+        undo.pos.clone_from(&self.pos);
+        undo.key = self.key;
+
+        let turn = self.pos.turn();
+        let castles = self.pos.castles().clone();
+
+        self.key ^= Zobrist64::zobrist_for_white_turn();
+        self.key ^= Self::ep_key(&self.pos);
+
+        match *m {
+            Move::Normal {
+                role,
+                from,
+                capture,
+                to,
+                promotion,
+            } => {
+                self.key ^= Zobrist64::zobrist_for_piece(from, role.of(turn));
+                self.key ^= Zobrist64::zobrist_for_piece(to, promotion.unwrap_or(role).of(turn));
+
+                if let Some(capture) = capture {
+                    self.key ^= Zobrist64::zobrist_for_piece(to, capture.of(!turn));
+                }
+            }
+
+            Move::EnPassant { from, to } => {
+                let captured = Square::from_coords(to.file(), from.rank());
+
+                self.key ^= Zobrist64::zobrist_for_piece(from, turn.pawn());
+                self.key ^= Zobrist64::zobrist_for_piece(to, turn.pawn());
+                self.key ^= Zobrist64::zobrist_for_piece(captured, (!turn).pawn());
+            }
+
+            Move::Castle { king, rook } => {
+                let side = CastlingSide::from_queen_side(rook < king);
+                let king_to = Square::from_coords(side.king_to_file(), king.rank());
+                let rook_to = Square::from_coords(side.rook_to_file(), rook.rank());
+
+                self.key ^= Zobrist64::zobrist_for_piece(king, turn.king());
+                self.key ^= Zobrist64::zobrist_for_piece(king_to, turn.king());
+                self.key ^= Zobrist64::zobrist_for_piece(rook, turn.rook());
+                self.key ^= Zobrist64::zobrist_for_piece(rook_to, turn.rook());
+            }
+
+            Move::Put { .. } => unreachable!("drops are not played in standard chess"),
+        }
+
+        self.pos.play_unchecked(m);
+
+        if castles.castling_rights() != self.pos.castles().castling_rights() {
+            self.key ^= Self::castling_key(&castles) ^ Self::castling_key(self.pos.castles());
+        }
+        self.key ^= Self::ep_key(&self.pos);
+
+        debug_assert!(self.key == self.pos.zobrist_hash(EnPassantMode::Legal));
+    }
+
+    /// Passes the move to the opponent.
+    ///
+    /// # Arguments
+    /// * `undo` - Undo information of the ply, overwritten
+    pub fn play_null(&mut self, undo: &mut Undo) {
+        undo.pos.clone_from(&self.pos);
+        undo.key = self.key;
+
+        self.key ^= Zobrist64::zobrist_for_white_turn();
+        self.key ^= Self::ep_key(&self.pos);
+
+        /* Not in check, so the position with the other side to move is valid */
+        self.pos = self.pos.clone().swap_turn().unwrap();
+
+        debug_assert!(self.key == self.pos.zobrist_hash(EnPassantMode::Legal));
+    }
+
+    /// Takes back the move played with the given undo information.
+    pub fn undo(&mut self, undo: &Undo) {
+        self.pos.clone_from(&undo.pos);
+        self.key = undo.key;
+    }
+
+    fn castling_key(castles: &Castles) -> Zobrist64 {
+        let mut key = Zobrist64::default();
+
+        for color in Color::ALL {
+            for side in CastlingSide::ALL {
+                if castles.has(color, side) {
+                    key ^= Zobrist64::zobrist_for_castling_right(color, side);
+                }
+            }
+        }
+
+        key
+    }
+
+    fn ep_key(pos: &Chess) -> Zobrist64 {
+        pos.ep_square(EnPassantMode::Legal)
+            .map_or(Zobrist64::default(), |sq| {
+                Zobrist64::zobrist_for_en_passant_file(sq.file())
+            })
+    }
+}
+
+impl Deref for SearchPosition {
+    type Target = Chess;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::CastlingMode;
+
+    fn position(fen: &str) -> SearchPosition {
+        let pos: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+
+        SearchPosition::new(pos)
+    }
+
+    /// Counts the leaf nodes at the given depth, taking every move back on the way up.
+    fn perft(pos: &mut SearchPosition, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = pos.legal_moves();
+        let mut undo = Undo::default();
+        let mut nodes = 0;
+
+        for m in moves.iter() {
+            pos.play(m, &mut undo);
+            nodes += perft(pos, depth - 1);
+            pos.undo(&undo);
+        }
+
+        nodes
+    }
+
+    /// Checks that every part of a position and its key are back to a saved state.
+    fn assert_restored(pos: &SearchPosition, original: &Chess, key: Zobrist64) {
+        assert_eq!(pos.board(), original.board());
+        assert_eq!(pos.turn(), original.turn());
+        assert_eq!(
+            pos.castles().castling_rights(),
+            original.castles().castling_rights()
+        );
+        assert_eq!(
+            pos.ep_square(EnPassantMode::Always),
+            original.ep_square(EnPassantMode::Always)
+        );
+        assert_eq!(pos.halfmoves(), original.halfmoves());
+        assert_eq!(pos.fullmoves(), original.fullmoves());
+        assert!(pos.key() == key);
+    }
+
+    /// Runs perft and checks that the position is restored afterwards.
+    fn assert_perft(fen: &str, depth: u32, expected: u64) {
+        let mut pos = position(fen);
+        let original = pos.pos.clone();
+        let key = pos.key();
+
+        assert_eq!(perft(&mut pos, depth), expected, "{}", fen);
+        assert_restored(&pos, &original, key);
+    }
+
+    #[test]
+    fn perft_start_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            4,
+            197281,
+        );
+    }
+
+    #[test]
+    fn perft_castling_and_en_passant() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+            97862,
+        );
+        assert_perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238);
+    }
+
+    #[test]
+    fn perft_promotions() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+            9467,
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            3,
+            62379,
+        );
+    }
+
+    #[test]
+    fn null_move_is_taken_back() {
+        let fen = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2";
+        let mut pos = position(fen);
+        let original = pos.pos.clone();
+        let key = pos.key();
+        let mut undo = Undo::default();
+
+        pos.play_null(&mut undo);
+        assert_eq!(pos.turn(), Color::White);
+        pos.undo(&undo);
+
+        assert_restored(&pos, &original, key);
+    }
+}
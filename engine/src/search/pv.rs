@@ -1,17 +1,26 @@
 //! Principal Variation (PV) handling module.
 //! Manages the storage and retrieval of best move sequences found during search.
 
-use shakmaty::{CastlingMode, Move};
+use crate::score::MAX_PLY;
+use shakmaty::Move;
 
 /// Represents a Principal Variation table storing the best move sequences.
 /// Uses a triangular table structure to efficiently store move sequences at different plies.
 pub struct PvTable {
     /// Stores the length of the principal variation at each ply
-    pub length: [i32; 64],
-    /// 2D array storing moves for each ply, with maximum depth of 64 plies
+    pub length: [i32; MAX_PLY as usize],
+    /// 2D array storing moves for each ply, with maximum depth of `MAX_PLY` plies
     pub table: Vec<Vec<Option<Move>>>,
 }
 
+/// A principal variation and its score, one per line in MultiPV mode.
+pub struct PvLine {
+    /// Score of the variation from the side to move's perspective
+    pub score: i32,
+    /// Moves of the variation, starting with the root move
+    pub moves: Vec<Move>,
+}
+
 impl PvTable {
     /// Creates a new PvTable with default values.
     ///
@@ -19,8 +28,8 @@ impl PvTable {
     /// * A new PvTable instance initialized with default moves and zero lengths
     pub fn default() -> PvTable {
         PvTable {
-            length: [0; 64],
-            table: vec![vec![None; 64]; 64],
+            length: [0; MAX_PLY as usize],
+            table: vec![vec![None; MAX_PLY as usize]; MAX_PLY as usize],
         }
     }
 }
@@ -50,20 +59,6 @@ impl PvTable {
         self.length[ply] = ply as i32;
     }
 
-    /// Collects the principal variation into a vector of UCI move strings.
-    ///
-    /// # Returns
-    /// * Vector of strings representing the moves in UCI format
-    pub fn collect(&self) -> Vec<String> {
-        self.table[0][0..self.length[0] as usize]
-            .iter()
-            .map(|m| match m {
-                Some(m) => m.to_uci(CastlingMode::Standard).to_string(),
-                None => "".to_string(),
-            })
-            .collect()
-    }
-
     /// Returns the best move from the principal variation.
     ///
     /// # Returns
@@ -71,4 +66,22 @@ impl PvTable {
     pub fn get_best_move(&self) -> Option<Move> {
         self.table[0][0].clone()
     }
+
+    /// Copies the principal variation found from the root.
+    ///
+    /// # Arguments
+    /// * `score` - Score of the variation
+    ///
+    /// # Returns
+    /// * The principal variation with its score
+    pub fn line(&self, score: i32) -> PvLine {
+        PvLine {
+            score,
+            moves: self.table[0][0..self.length[0] as usize]
+                .iter()
+                .flatten()
+                .cloned()
+                .collect(),
+        }
+    }
 }
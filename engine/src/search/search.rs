@@ -1,33 +1,128 @@
 use crate::bound::Bound;
 use crate::eval::Eval;
 use crate::logger::Logger;
-use crate::moves::DEFAULT_MOVE;
+use crate::moves::{pack_move, unpack_move, DEFAULT_MOVE};
+use crate::nnue::NNUEState;
 use crate::nnue::OFF;
 use crate::nnue::ON;
-use crate::time_control::time_mode::TimeMode;
-use shakmaty::zobrist::{Zobrist64, ZobristHash};
-use shakmaty::{CastlingMode, CastlingSide, Chess, EnPassantMode, Move, Piece, Position, Square};
-
-use super::move_picker::MovePicker;
+use crate::score::{self, INFINITY, MATE, MATE_BOUND, MAX_PLY};
+use shakmaty::{CastlingMode, CastlingSide, Move, Piece, Position, Square};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::history;
+use super::move_picker::{MovePicker, Stage};
+use super::position::SearchPosition;
+use super::pv::PvLine;
+use super::see;
 use super::SearchState;
 
+/// Interval at which a thread publishes its node count, must be a power of two.
+const NODES_PUBLISH_INTERVAL: u64 = 1024;
+/// Time in milliseconds after which the root move being searched is reported.
+const CURRMOVE_DELAY: u128 = 3000;
+
 pub struct Search {
     pub state: SearchState,
+    /// Helper searches sharing the transposition table, run on their own
+    /// threads when `Threads` is greater than 1 (Lazy SMP)
+    helpers: Vec<Search>,
+    /// Node counters of the helpers while they run, empty for helpers
+    helper_nodes: Vec<Arc<AtomicU64>>,
+    /// Whether the search reports its progress, never set for helpers
+    print: bool,
 }
 
 impl Search {
     pub fn new() -> Self {
         Self {
             state: SearchState::new(),
+            helpers: Vec::new(),
+            helper_nodes: Vec::new(),
+            print: false,
+        }
+    }
+
+    /// Resets the search for a new game: the transposition table and the
+    /// per-game state of every thread.
+    pub fn new_game(&mut self) {
+        self.state.tt.clear();
+        self.state.new_game();
+
+        for helper in self.helpers.iter_mut() {
+            helper.state.new_game();
+        }
+    }
+
+    /// Sets the number of threads used by the search.
+    /// Helpers keep their own history, killers and NNUE state but share
+    /// the transposition table and the stop flag of the main search.
+    ///
+    /// # Arguments
+    /// * `threads` - Total number of threads, including the main one
+    pub fn set_threads(&mut self, threads: usize) {
+        self.helpers = (1..threads.max(1))
+            .map(|_| Search {
+                state: SearchState::with_shared(
+                    Arc::clone(&self.state.tt),
+                    Arc::clone(&self.state.stop),
+                    self.state.cfg.clone(),
+                ),
+                helpers: Vec::new(),
+                helper_nodes: Vec::new(),
+                print: false,
+            })
+            .collect();
+    }
+
+    /// Returns the number of nodes searched by all threads.
+    pub fn total_nodes(&self) -> u64 {
+        self.state.info.nodes
+            + self
+                .helpers
+                .iter()
+                .map(|h| h.state.info.shared_nodes.load(Ordering::Relaxed))
+                .sum::<u64>()
+    }
+
+    /// Copies the root position and limits of the main search into a helper.
+    ///
+    /// # Arguments
+    /// * `main` - State of the main search
+    fn sync_helper(&mut self, main: &SearchState) {
+        self.state.game = main.game.clone();
+        self.state.hstack = main.hstack.clone();
+        self.state.nnue = NNUEState::from_board(main.game.board());
+        self.state.params.depth = main.params.depth;
+        self.state.params.search_moves = main.params.search_moves.clone();
+        self.state.cfg = main.cfg.clone();
+        self.state.tt = Arc::clone(&main.tt);
+        self.state.info.nodes = 0;
+        self.state.info.shared_nodes.store(0, Ordering::Relaxed);
+    }
+
+    /// Counts a visited node and periodically publishes the count for the main thread.
+    fn count_node(&mut self) {
+        self.state.info.nodes += 1;
+
+        if self.state.info.nodes & (NODES_PUBLISH_INTERVAL - 1) == 0 {
+            self.state
+                .info
+                .shared_nodes
+                .store(self.state.info.nodes, Ordering::Relaxed);
         }
     }
+
     /// Makes a move on the board while updating NNUE (Neural Network) accumulator states.
     /// This method should be used instead of regular make_move when NNUE evaluation is active.
     ///
     /// # Arguments
     /// * `pos` - Mutable reference to the chess position
     /// * `m` - Reference to the move to be played
-    pub fn make_move(&mut self, pos: &mut Chess, m: &Move, eval: i32) {
+    /// * `ply` - Ply the move is played from, its undo information is kept there
+    pub fn make_move(&mut self, pos: &mut SearchPosition, m: &Move, ply: usize) {
         self.state.nnue.push();
         let turn = pos.turn();
         let board = pos.board();
@@ -93,78 +188,359 @@ impl Search {
             _ => {}
         }
 
-        pos.play_unchecked(m);
-        self.state
-            .hstack
-            .push(pos.zobrist_hash(EnPassantMode::Legal), Some(eval));
+        pos.play(m, &mut self.state.stack[ply].undo);
+        self.state.hstack.push(pos.key());
     }
 
-    /// Reverts the last move made with make_move_nnue.
-    /// Restores NNUE state and removes last position from history.
-    pub fn undo_move(&mut self) {
+    /// Reverts the last move made with make_move.
+    /// Restores the position and NNUE state and removes last position from history.
+    ///
+    /// # Arguments
+    /// * `pos` - Mutable reference to the chess position
+    /// * `ply` - Ply the move was played from
+    pub fn undo_move(&mut self, pos: &mut SearchPosition, ply: usize) {
+        pos.undo(&self.state.stack[ply].undo);
         self.state.nnue.pop();
         self.state.hstack.pop();
     }
 
-    /// Starts the search process using iterative deepening.
+    /// Passes the move to the opponent, keeping the NNUE and history stacks
+    /// aligned with the search ply.
+    ///
+    /// # Arguments
+    /// * `pos` - Mutable reference to the chess position, not in check
+    /// * `ply` - Ply the null move is played from, its undo information is kept there
+    pub fn make_null_move(&mut self, pos: &mut SearchPosition, ply: usize) {
+        self.state.stack[ply].played = None;
+        self.state.stack[ply].cont_hist = None;
+        self.state.stack[ply + 1].double_extensions = self.state.stack[ply].double_extensions;
+
+        self.state.nnue.push();
+        pos.play_null(&mut self.state.stack[ply].undo);
+        self.state.hstack.push_null(pos.key());
+    }
+
+    /// Reverts the null move made with make_null_move.
+    ///
+    /// # Arguments
+    /// * `pos` - Mutable reference to the chess position
+    /// * `ply` - Ply the null move was played from
+    pub fn undo_null_move(&mut self, pos: &mut SearchPosition, ply: usize) {
+        pos.undo(&self.state.stack[ply].undo);
+        self.state.nnue.pop();
+        self.state.hstack.pop();
+    }
+
+    /// Checks whether the search has to be interrupted, either because the
+    /// allocated time is over or because a `stop` command was received.
+    /// The clock only starts running once a ponder search gets a `ponderhit`.
+    fn should_stop(&mut self) -> bool {
+        if !self.state.info.aborted {
+            self.state.info.aborted = self.is_interrupted();
+        }
+
+        self.state.info.aborted
+    }
+
+    /// Checks the stop flag, the node limit and the clock, see `should_stop`.
+    fn is_interrupted(&mut self) -> bool {
+        if self.state.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if self.state.tc.pondering {
+            if self.state.ponder.load(Ordering::Relaxed) {
+                return false;
+            }
+
+            self.state.tc.ponderhit();
+        }
+
+        /* The node limit counts the nodes of every thread */
+        if let Some(nodes) = self.state.params.nodes {
+            if self.reported_nodes() >= nodes {
+                return true;
+            }
+        }
+
+        /* The time limits only apply once the first iteration has given a move */
+        self.state.info.depth > 1 && self.state.tc.is_time_up(self.state.info.nodes)
+    }
+
+    /// Blocks until the GUI allows the best move to be sent, as a ponder or
+    /// infinite search must not report it before `ponderhit` or `stop`.
+    fn wait_for_release(&self) {
+        while !self.state.stop.load(Ordering::Relaxed)
+            && (self.state.params.infinite || self.state.ponder.load(Ordering::Relaxed))
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Starts the search process, running the helpers on their own threads
+    /// alongside the main search. The main search picks the best move.
     /// Prints search information and best move when complete.
     pub fn go(&mut self, print: bool) {
+        self.print = print;
         self.state
             .tc
             .setup(&self.state.params, &self.state.game, &self.state.cfg);
-        self.state.hist.new_search();
-        self.state.info.nodes = 0;
         self.state.tt.new_search();
 
-        let mut best_move = DEFAULT_MOVE.clone();
+        let mut helpers = std::mem::take(&mut self.helpers);
+        self.helper_nodes = helpers
+            .iter()
+            .map(|h| Arc::clone(&h.state.info.shared_nodes))
+            .collect();
+
+        let (best_move, ponder_move) = thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                helper.sync_helper(&self.state);
+
+                thread::Builder::new()
+                    .stack_size(8 * 1024 * 1024) // 8MB stack size
+                    .spawn_scoped(scope, || helper.iterative_deepening())
+                    .expect("Thread creation failed");
+            }
+
+            let result = self.iterative_deepening();
+
+            if print {
+                self.wait_for_release();
+            }
+
+            /* Helpers search until told otherwise */
+            if !self.helper_nodes.is_empty() {
+                self.state.stop.store(true, Ordering::Relaxed);
+            }
+
+            result
+        });
+
+        self.helpers = helpers;
+        self.helper_nodes.clear();
+
+        if print {
+            /* No legal move at the root */
+            let best_move = match best_move == DEFAULT_MOVE {
+                true => "0000".to_string(),
+                false => best_move.to_uci(CastlingMode::Standard).to_string(),
+            };
+
+            match ponder_move {
+                Some(m) => Logger::log(&format!(
+                    "bestmove {} ponder {}",
+                    best_move,
+                    m.to_uci(CastlingMode::Standard)
+                )),
+                None => Logger::log(&format!("bestmove {}", best_move)),
+            }
+        }
+    }
+
+    /// Runs the iterative deepening loop of a single thread.
+    ///
+    /// # Returns
+    /// * Best move of the last completed iteration and the expected reply to ponder on
+    fn iterative_deepening(&mut self) -> (Move, Option<Move>) {
+        self.state.hist.new_search();
+        for cont_hist in self.state.cont_hist.iter_mut() {
+            cont_hist.new_search();
+        }
+        self.state.stack.clear();
+        self.state.info.nodes = 0;
+        self.state.info.aborted = false;
+
+        /* Any legal move beats a null move if even the first iteration is interrupted */
+        let mut best_move = match self.state.params.search_moves.first() {
+            Some(m) => m.clone(),
+            None => self
+                .state
+                .game
+                .legal_moves()
+                .first()
+                .cloned()
+                .unwrap_or(DEFAULT_MOVE.clone()),
+        };
+        let mut ponder_move = None;
+        let root_moves = match self.state.params.search_moves.len() {
+            0 => self.state.game.legal_moves().len(),
+            restricted => restricted,
+        };
+        let multi_pv = self.state.cfg.multi_pv.value.min(root_moves).max(1);
+        let mut prev_scores: Vec<i32> = Vec::with_capacity(multi_pv);
 
         /* Iterative deepening */
-        for current_depth in 0..self.state.params.depth {
-            if TimeMode::is_finite(&self.state.tc.time_mode)
-                && (self.state.tc.elapsed() * self.state.cfg.tc_elapsed_factor.value) as u128
-                    > self.state.tc.play_time
+        'deepening: for current_depth in 0..self.state.params.depth {
+            if current_depth > 0
+                && !self.state.tc.pondering
+                && self.state.tc.is_soft_time_up(&self.state.cfg)
             {
                 break;
             }
 
             self.state.info.depth = current_depth + 1;
-            let pos = self.state.game.clone();
-            let iteration_score = self.negamax(&pos, self.state.info.depth, -100000, 100000, 0);
+            self.state.info.sel_depth = 0;
+            self.state.root_excluded.clear();
+
+            let mut lines: Vec<PvLine> = Vec::with_capacity(multi_pv);
+            let mut iteration_nodes = 0;
+
+            /* MultiPV: each line searches the root without the best moves of the previous ones */
+            for k in 0..multi_pv {
+                let mut pos = SearchPosition::new(self.state.game.clone());
+                let nodes_before = self.state.info.nodes;
+                let mut delta = self.state.cfg.asp_delta.value;
+                let (mut alpha, mut beta) = match prev_scores.get(k) {
+                    Some(&prev) if self.state.info.depth >= self.state.cfg.asp_depth.value => {
+                        ((prev - delta).max(-INFINITY), (prev + delta).min(INFINITY))
+                    }
+                    _ => (-INFINITY, INFINITY),
+                };
+
+                /* Aspiration windows: search around the previous score, widen on failure */
+                let score = loop {
+                    let score = self.negamax(&mut pos, self.state.info.depth, alpha, beta, 0);
+
+                    if self.should_stop() {
+                        /* Keep the partial result if even the first iteration was interrupted */
+                        if current_depth == 0 && lines.is_empty() {
+                            best_move = self.state.pv.get_best_move().unwrap_or(best_move);
+                        }
+
+                        break 'deepening;
+                    }
+
+                    let bound = match score {
+                        score if score <= alpha => {
+                            beta = (alpha + beta) / 2;
+                            alpha = (score - delta).max(-INFINITY);
+                            Bound::Alpha
+                        }
+                        score if score >= beta => {
+                            beta = (score + delta).min(INFINITY);
+                            Bound::Beta
+                        }
+                        _ => break score,
+                    };
 
-            if self.state.tc.is_time_up() {
+                    if self.print {
+                        let line = self.state.pv.line(score);
+                        self.print_info(&line, k + 1, self.reported_nodes(), bound);
+                    }
+
+                    delta += (delta * (self.state.cfg.asp_growth.value - 100) / 100).max(1);
+                };
+
+                if lines.is_empty() {
+                    iteration_nodes = self.state.info.nodes - nodes_before;
+                }
+
+                let line = self.state.pv.line(score);
+
+                /* Checkmate or stalemate at the root, there is no move to report */
+                if line.moves.is_empty() {
+                    break;
+                }
+
+                self.state.root_excluded.push(line.moves[0].clone());
+                lines.push(line);
+            }
+
+            if lines.is_empty() {
                 break;
             }
 
-            best_move = self.state.pv.get_best_move().unwrap();
+            /* Stable sort, lines with equal scores keep their search order */
+            lines.sort_by_key(|line| -line.score);
 
-            let elapsed = self.state.tc.elapsed();
-            let pv = self.state.pv.collect();
+            best_move = lines[0].moves[0].clone();
+            ponder_move = lines[0].moves.get(1).cloned();
+            prev_scores = lines.iter().map(|line| line.score).collect();
 
-            if print {
-                Logger::log(&format!(
-                    "info depth {} nodes {} nps {} score cp {} time {} pv {}",
-                    self.state.info.depth,
-                    self.state.info.nodes,
-                    self.state.info.nodes as u128 * 1000 / (elapsed + 1) as u128,
-                    iteration_score,
-                    elapsed,
-                    pv.join(" ")
-                ));
+            self.state.tc.update(
+                &best_move,
+                lines[0].score,
+                self.state.info.best_move_nodes,
+                iteration_nodes,
+                &self.state.cfg,
+            );
+
+            if self.print {
+                let nodes = self.reported_nodes();
+
+                for (i, line) in lines.iter().enumerate() {
+                    self.print_info(line, i + 1, nodes, Bound::Exact);
+                }
             }
-        }
 
-        if print {
-            Logger::log(&format!(
-                "bestmove {}",
-                best_move.to_uci(CastlingMode::Standard)
-            ));
+            /* go mate: stop once a mate within the requested number of moves is found */
+            if let Some(mate) = self.state.params.mate {
+                if matches!(score::mate_in(lines[0].score), Some(n) if n > 0 && n <= mate as i32) {
+                    break;
+                }
+            }
         }
+
+        self.state.root_excluded.clear();
+
+        self.state
+            .info
+            .shared_nodes
+            .store(self.state.info.nodes, Ordering::Relaxed);
+
+        (best_move, ponder_move)
+    }
+
+    /// Returns the number of nodes searched by this thread and its helpers.
+    fn reported_nodes(&self) -> u64 {
+        self.state.info.nodes
+            + self
+                .helper_nodes
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed))
+                .sum::<u64>()
+    }
+
+    /// Prints the UCI info line of a principal variation.
+    ///
+    /// # Arguments
+    /// * `line` - Principal variation and its score
+    /// * `multi_pv` - Rank of the line among the MultiPV lines, starting at 1
+    /// * `nodes` - Number of nodes searched by all threads
+    /// * `bound` - `Alpha` or `Beta` when the score comes from a failed aspiration window
+    fn print_info(&self, line: &PvLine, multi_pv: usize, nodes: u64, bound: Bound) {
+        let elapsed = self.state.tc.elapsed();
+        let pv: Vec<String> = line
+            .moves
+            .iter()
+            .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+            .collect();
+        let bound = match bound {
+            Bound::Alpha => " upperbound",
+            Bound::Beta => " lowerbound",
+            Bound::Exact => "",
+        };
+
+        Logger::log(&format!(
+            "info depth {} seldepth {} multipv {} nodes {} nps {} hashfull {} score {}{} time {} pv {}",
+            self.state.info.depth,
+            self.state.info.sel_depth,
+            multi_pv,
+            nodes,
+            nodes as u128 * 1000 / (elapsed + 1),
+            self.state.tt.hashfull(),
+            score::to_uci(line.score),
+            bound,
+            elapsed,
+            pv.join(" ")
+        ));
     }
 
     /// Performs negamax search with alpha-beta pruning and various optimizations.
     ///
     /// # Arguments
-    /// * `pos` - Reference to current chess position
+    /// * `pos` - Current chess position, moves are played and taken back on it
     /// * `depth` - Remaining search depth
     /// * `alpha` - Alpha value for alpha-beta pruning
     /// * `beta` - Beta value for alpha-beta pruning
@@ -174,7 +550,7 @@ impl Search {
     /// * Score of the position from the perspective of the side to move
     fn negamax(
         &mut self,
-        pos: &Chess,
+        pos: &mut SearchPosition,
         mut depth: u8,
         mut alpha: i32,
         beta: i32,
@@ -182,45 +558,65 @@ impl Search {
     ) -> i32 {
         self.state.pv.update_length(ply);
 
-        if self.state.tc.is_time_up() {
+        if self.should_stop() {
             return 0;
         }
 
         if depth == 0 {
-            return self.quiesce(pos, alpha, beta, self.state.cfg.qsearch_depth.value);
+            return self.quiesce(pos, alpha, beta, self.state.cfg.qsearch_depth.value, ply);
         }
 
-        self.state.info.nodes += 1;
+        self.count_node();
+        self.state.info.sel_depth = self.state.info.sel_depth.max(ply);
+
+        if ply >= MAX_PLY as usize - 1 {
+            return Eval::nnue_eval(&self.state.nnue, pos);
+        }
 
         let is_root = ply == 0;
-        let position_key = pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
-        let entry = self.state.tt.probe(position_key);
-
-        /* Transposition Table Cut-offs */
-        if entry.key == position_key
-            && entry.generation == self.state.tt.generation
-            && !is_root
-            && entry.depth >= depth
-            && (entry.bound == Bound::Exact
-                || (entry.bound == Bound::Alpha && entry.score <= alpha)
-                || (entry.bound == Bound::Beta && entry.score >= beta))
-        {
-            return entry.score;
+        let position_key = pos.key();
+        let excluded = self.state.stack[ply].excluded.clone();
+        let entry = self.state.tt.probe(position_key, ply);
+        let tt_move = entry.as_ref().and_then(|e| unpack_move(e._move, pos));
+
+        /* Transposition Table Cut-offs, the entry does not apply when a move is excluded */
+        if let Some(entry) = &entry {
+            if entry.generation == self.state.tt.generation()
+                && !is_root
+                && excluded.is_none()
+                && entry.depth >= depth
+                && (entry.bound == Bound::Exact
+                    || (entry.bound == Bound::Alpha && entry.score <= alpha)
+                    || (entry.bound == Bound::Beta && entry.score >= beta))
+            {
+                return entry.score;
+            }
         }
 
-        let static_eval = Eval::nnue_eval(&self.state.nnue, pos);
+        /* Reuse the static eval of a transposition, saving an inference */
+        let eval = match &entry {
+            Some(entry) => entry.eval,
+            None => Eval::nnue_eval(&self.state.nnue, pos),
+        };
+
+        /* The TT score is a better estimate than the static eval when its bound allows it */
+        let static_eval = match &entry {
+            Some(entry)
+                if !score::is_mate(entry.score)
+                    && (entry.bound == Bound::Exact
+                        || (entry.bound == Bound::Beta && entry.score > eval)
+                        || (entry.bound == Bound::Alpha && entry.score < eval)) =>
+            {
+                entry.score
+            }
+            _ => eval,
+        };
+
+        self.state.stack[ply].eval = Some(eval);
+
         let improving = match ply {
             ply if ply < 2 => false,
-            _ => {
-                static_eval >= {
-                    let e = self.state.hstack.get_eval(ply - 2);
-                    if let Some(e) = e {
-                        e
-                    } else {
-                        static_eval
-                    }
-                }
-            }
+            _ => eval >= self.state.stack[ply - 2].eval.unwrap_or(eval),
         };
 
         if ply > 0 && self.state.hstack.count_zobrist(position_key) >= 1 {
@@ -231,8 +627,14 @@ impl Search {
         let is_check = pos.is_check();
 
         if !is_check && !is_pv {
-            /* Null Move Pruning */
-            if depth > self.state.cfg.nmp_depth.value && ply > 0 && Eval::has_pieces(pos) {
+            /* Null Move Pruning, never twice in a row */
+            if depth > self.state.cfg.nmp_depth.value
+                && ply > 0
+                && excluded.is_none()
+                && ply >= self.state.info.nmp_min_ply
+                && self.state.stack[ply - 1].played.is_some()
+                && Eval::has_pieces(pos)
+            {
                 let r = match improving {
                     true => (self.state.cfg.nmp_margin.value
                         + depth / self.state.cfg.nmp_divisor_improving.value)
@@ -242,11 +644,31 @@ impl Search {
                         .min(depth),
                 };
 
-                let pos = pos.clone().swap_turn().unwrap();
-                let score = -self.negamax(&pos, depth - r, -beta, -beta + 1, ply + 1);
+                self.make_null_move(pos, ply);
+                let score = -self.negamax(pos, depth - r, -beta, -beta + 1, ply + 1);
+                self.undo_null_move(pos, ply);
 
                 if score >= beta {
-                    return score;
+                    /* Do not trust a mate found after passing the move */
+                    let score = match score::is_mate(score) {
+                        true => beta,
+                        false => score,
+                    };
+
+                    if self.state.info.nmp_min_ply > 0
+                        || depth < self.state.cfg.nmp_verification_depth.value
+                    {
+                        return score;
+                    }
+
+                    /* Verification search without null moves near the root, guards against zugzwang */
+                    self.state.info.nmp_min_ply = ply + 3 * (depth - r) as usize / 4;
+                    let verified = self.negamax(pos, depth - r, beta - 1, beta, ply);
+                    self.state.info.nmp_min_ply = 0;
+
+                    if verified >= beta {
+                        return score;
+                    }
                 }
             }
 
@@ -266,44 +688,133 @@ impl Search {
             }
         }
 
-        let moves = pos.legal_moves();
-
-        let mp = MovePicker::new(&moves, &self.state, &entry, ply);
-
-        if !moves.contains(&entry._move) && depth > 1 {
+        if tt_move.is_none() && depth > 1 {
             depth -= 1;
         }
 
-        /* Checkmate/Draw Detection */
-        if moves.is_empty() {
-            return match pos.is_checkmate() {
-                true => -100000 + ply as i32,
-                false => 0,
-            };
-        }
+        let counter_move = ply
+            .checked_sub(1)
+            .and_then(|p| self.state.stack[p].cont_hist)
+            .and_then(|prev| self.state.counter_moves.get(prev).cloned());
+        let mut mp = MovePicker::new(tt_move, counter_move, ply);
 
         let start_alpha = alpha;
-        let mut best_score = -100000;
-        let mut skip_quiets = false;
-        let mut best_move = &moves[0];
+        let mut best_score = -INFINITY;
+        let mut best_move = DEFAULT_MOVE.clone();
+        let mut move_count = 0;
+        let mut quiets_tried: Vec<Move> = Vec::new();
 
-        for (i, m) in mp.enumerate() {
-            if skip_quiets && (!m.is_promotion() && !m.is_capture()) {
+        while let Some(m) = mp.next(pos, &self.state) {
+            if excluded.as_ref() == Some(&m) {
                 continue;
             }
 
-            if !m.is_capture()
+            let i = move_count;
+            move_count += 1;
+
+            /* Late Move Pruning: every later quiet move is pruned as well */
+            if mp.stage() == Stage::Quiets
                 && !is_pv
-                && !m.is_promotion()
                 && !is_check
                 && i >= self.state.cfg.lmp_move_margin.value
                     + (self.state.cfg.lmp_depth_factor.value * depth) as usize
             {
+                mp.skip_quiets();
                 continue;
             }
 
-            let mut pos = pos.clone();
-            self.make_move(&mut pos, m, static_eval);
+            /* SEE Pruning: skip moves losing too much material at low depth */
+            if !is_root
+                && !is_check
+                && !matches!(mp.stage(), Stage::GoodCaptures | Stage::LowCaptures)
+                && best_score > -MATE_BOUND
+                && depth <= self.state.cfg.see_depth.value
+            {
+                let margin = match m.is_capture() {
+                    true => self.state.cfg.see_capture_margin.value,
+                    false => self.state.cfg.see_quiet_margin.value,
+                };
+
+                if !see::see(pos, &m, -margin * depth as i32) {
+                    continue;
+                }
+            }
+
+            if is_root && self.print && self.state.tc.elapsed() > CURRMOVE_DELAY {
+                Logger::log(&format!(
+                    "info depth {} currmove {} currmovenumber {}",
+                    self.state.info.depth,
+                    m.to_uci(CastlingMode::Standard),
+                    i + 1
+                ));
+            }
+
+            let mut extension = 0;
+            let can_extend = ply < 2 * self.state.info.depth as usize;
+
+            /* Singular Extensions: the TT move is extended when every other move fails low */
+            if let Some(entry) = entry.as_ref().filter(|_| mp.stage() == Stage::TTMove) {
+                if can_extend
+                    && !is_root
+                    && excluded.is_none()
+                    && depth >= self.state.cfg.se_depth.value
+                    && entry
+                        .depth
+                        .saturating_add(self.state.cfg.se_tt_depth_margin.value)
+                        >= depth
+                    && entry.bound != Bound::Alpha
+                    && !score::is_mate(entry.score)
+                {
+                    let singular_beta =
+                        entry.score - self.state.cfg.se_beta_margin.value * depth as i32;
+
+                    self.state.stack[ply].excluded = Some(m.clone());
+                    let score =
+                        self.negamax(pos, (depth - 1) / 2, singular_beta - 1, singular_beta, ply);
+                    self.state.stack[ply].excluded = None;
+
+                    if score < singular_beta {
+                        extension = match !is_pv
+                            && score < singular_beta - self.state.cfg.se_double_margin.value
+                            && self.state.stack[ply].double_extensions
+                                < self.state.cfg.se_double_limit.value
+                        {
+                            true => 2,
+                            false => 1,
+                        };
+                    } else if singular_beta >= beta {
+                        /* Multi-cut: several moves beat beta, the node is very likely a cut-node */
+                        return singular_beta;
+                    } else if entry.score >= beta {
+                        /* Negative extension: another move is likely to cut as well */
+                        extension = -1;
+                    }
+                }
+            }
+
+            let nodes_before = self.state.info.nodes;
+            let piece = Piece {
+                color: pos.turn(),
+                role: m.role(),
+            };
+            let is_quiet = !m.is_capture() && !m.is_promotion();
+            let hist_score = match is_quiet {
+                true => self.state.quiet_history(ply, piece, m.to()),
+                false => 0,
+            };
+            self.state.stack[ply].cont_hist = Some((piece, m.to()));
+            self.state.stack[ply].played = Some(m.clone());
+
+            self.make_move(pos, &m, ply);
+
+            /* Check Extension */
+            if can_extend && extension == 0 && pos.is_check() {
+                extension = 1;
+            }
+
+            self.state.stack[ply + 1].double_extensions =
+                self.state.stack[ply].double_extensions + (extension == 2) as u8;
+            let new_depth = (depth as i32 - 1 + extension) as u8;
 
             let mut score: i32;
             let mut r = 1;
@@ -312,7 +823,7 @@ impl Search {
                 && i >= self.state.cfg.lmr_move_margin.value
                 && !pos.is_check()
             {
-                r = match m {
+                r = match &m {
                     m if m.is_capture() || m.is_promotion() => {
                         (self.state.cfg.lmr_base_margin.value
                             + (depth as f64).ln() * (i as f64).ln()
@@ -332,6 +843,12 @@ impl Search {
                     r *= 2;
                 }
 
+                /* Quiet moves with a good history are reduced less, bad ones more */
+                if is_quiet {
+                    r = (r as i32 - hist_score / self.state.cfg.lmr_history_divisor.value)
+                        .clamp(1, depth as i32) as u8;
+                }
+
                 r = r.clamp(1, depth);
             }
 
@@ -341,42 +858,84 @@ impl Search {
                         + self.state.cfg.fp_margin_depth_factor.value * (depth - r) as i32)
                     < alpha
             {
-                skip_quiets = true;
+                mp.skip_quiets();
             }
 
             /* Principal Variation Search */
             match i {
-                0 => score = -self.negamax(&pos, depth - 1, -beta, -alpha, ply + 1),
+                0 => score = -self.negamax(pos, new_depth, -beta, -alpha, ply + 1),
                 _ => {
-                    score = -self.negamax(&pos, depth - r, -(alpha + 1), -alpha, ply + 1);
+                    let reduced_depth = new_depth.saturating_sub(r - 1);
+                    score = -self.negamax(pos, reduced_depth, -(alpha + 1), -alpha, ply + 1);
 
                     if score > alpha && beta - alpha > 1 {
-                        score = -self.negamax(&pos, depth - 1, -beta, -alpha, ply + 1);
+                        score = -self.negamax(pos, new_depth, -beta, -alpha, ply + 1);
                     }
                 }
             }
 
-            self.undo_move();
+            self.undo_move(pos, ply);
+
+            /* The score of an interrupted search must not reach the PV, the heuristics or the TT */
+            if self.state.info.aborted {
+                return 0;
+            }
 
             if score > best_score {
                 best_score = score;
-                best_move = m;
+                best_move = m.clone();
+
+                if is_root && self.state.root_excluded.is_empty() {
+                    self.state.info.best_move_nodes = self.state.info.nodes - nodes_before;
+                }
 
                 if best_score > alpha {
-                    self.state.pv.store(ply, best_move.clone());
+                    self.state.pv.store(ply, m.clone());
                     alpha = best_score;
                 }
             }
 
             if score >= beta {
-                self.state.km.store(ply, m.clone());
+                if is_quiet {
+                    let bonus = history::bonus(depth);
+
+                    self.state.stack.store_killer(ply, &m);
+                    self.state.update_quiet_history(ply, piece, m.to(), bonus);
+
+                    /* The quiet moves searched before the cut-off did not refute the position */
+                    for quiet in &quiets_tried {
+                        let piece = Piece {
+                            color: pos.turn(),
+                            role: quiet.role(),
+                        };
 
-                if !m.is_capture() {
-                    self.state.hist.update(m.role(), m.to(), depth as i32);
+                        self.state
+                            .update_quiet_history(ply, piece, quiet.to(), -bonus);
+                    }
+
+                    if let Some(prev) = ply
+                        .checked_sub(1)
+                        .and_then(|p| self.state.stack[p].cont_hist)
+                    {
+                        self.state.counter_moves.store(prev, &m);
+                    }
                 }
 
                 break;
             }
+
+            if is_quiet {
+                quiets_tried.push(m);
+            }
+        }
+
+        /* Checkmate/Draw Detection, a singular search with no other move fails low */
+        if move_count == 0 {
+            return match is_check {
+                _ if excluded.is_some() => alpha,
+                true => -MATE + ply as i32,
+                false => 0,
+            };
         }
 
         let bound = match best_score {
@@ -385,9 +944,22 @@ impl Search {
             _ => Bound::Exact,
         };
 
-        self.state
-            .tt
-            .store(position_key, depth, best_score, bound, best_move.clone());
+        if self.state.info.aborted {
+            return 0;
+        }
+
+        /* A node searched without some of its moves must not overwrite the full entry */
+        if excluded.is_none() && (!is_root || self.state.root_excluded.is_empty()) {
+            self.state.tt.store(
+                position_key,
+                depth,
+                best_score,
+                eval,
+                bound,
+                pack_move(&best_move),
+                ply,
+            );
+        }
 
         best_score
     }
@@ -395,19 +967,32 @@ impl Search {
     /// Performs quiescence search to evaluate tactical sequences.
     ///
     /// # Arguments
-    /// * `pos` - Reference to current chess position
+    /// * `pos` - Current chess position, moves are played and taken back on it
     /// * `alpha` - Alpha value for alpha-beta pruning
     /// * `beta` - Beta value for alpha-beta pruning
     /// * `limit` - Maximum remaining depth for quiescence search
+    /// * `ply` - Current ply (half-move) in search
     ///
     /// # Returns
     /// * Static evaluation or tactical sequence evaluation
-    fn quiesce(&mut self, pos: &Chess, mut alpha: i32, beta: i32, limit: u8) -> i32 {
-        self.state.info.nodes += 1;
+    fn quiesce(
+        &mut self,
+        pos: &mut SearchPosition,
+        mut alpha: i32,
+        beta: i32,
+        limit: u8,
+        ply: usize,
+    ) -> i32 {
+        if self.state.stop.load(Ordering::Relaxed) {
+            return 0;
+        }
+
+        self.count_node();
+        self.state.info.sel_depth = self.state.info.sel_depth.max(ply);
 
         let stand_pat = Eval::nnue_eval(&self.state.nnue, pos);
 
-        if limit == 0 {
+        if limit == 0 || ply >= MAX_PLY as usize - 1 {
             return stand_pat;
         }
         if stand_pat >= beta {
@@ -420,10 +1005,14 @@ impl Search {
         let moves = pos.capture_moves();
 
         for m in moves {
-            let mut pos = pos.clone();
-            self.make_move(&mut pos, &m, stand_pat);
-            let score = -self.quiesce(&pos, -beta, -alpha, limit - 1);
-            self.undo_move();
+            /* Losing captures cannot raise alpha */
+            if !see::see(pos, &m, 0) {
+                continue;
+            }
+
+            self.make_move(pos, &m, ply);
+            let score = -self.quiesce(pos, -beta, -alpha, limit - 1, ply + 1);
+            self.undo_move(pos, ply);
 
             if score >= beta {
                 return beta;
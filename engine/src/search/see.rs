@@ -0,0 +1,181 @@
+//! Static Exchange Evaluation.
+//! Resolves the sequence of captures on the target square of a move, each
+//! side recapturing with its least valuable attacker, to tell whether the
+//! move wins material without searching it.
+
+use shakmaty::{Bitboard, Chess, Color, Move, Position, Role, Square};
+
+/// Piece values used by the exchange, indexed by `Role as usize - 1`.
+/// The king is worth nothing as it can only capture last.
+const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 0];
+
+/// Roles from the least to the most valuable attacker.
+const ATTACKER_ORDER: [Role; 6] = [
+    Role::Pawn,
+    Role::Knight,
+    Role::Bishop,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+fn value(role: Role) -> i32 {
+    SEE_VALUES[role as usize - 1]
+}
+
+/// Checks if a move wins at least `threshold` material once every capture on
+/// its target square has been played out. Pins are not taken into account.
+///
+/// # Arguments
+/// * `pos` - Position before the move
+/// * `m` - Move to evaluate
+/// * `threshold` - Material balance the move must reach, in centipawns
+///
+/// # Returns
+/// * `true` if the exchange ends with a balance of at least `threshold`
+pub fn see(pos: &Chess, m: &Move, threshold: i32) -> bool {
+    let (from, to) = match m {
+        Move::Normal { from, to, .. } => (*from, *to),
+        Move::EnPassant { from, to } => (*from, *to),
+        _ => return threshold <= 0,
+    };
+
+    let board = pos.board();
+    let promotion = m
+        .promotion()
+        .map_or(0, |role| value(role) - value(Role::Pawn));
+
+    /* Balance after the move, assuming the piece is lost afterwards */
+    let mut swap = m.capture().map_or(0, value) + promotion - threshold;
+    if swap < 0 {
+        return false;
+    }
+
+    /* Balance if the piece is not recaptured */
+    swap = value(m.promotion().unwrap_or(m.role())) - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    let mut occupied = board.occupied();
+    occupied.toggle(from);
+    occupied.toggle(to);
+    if let Move::EnPassant { .. } = m {
+        occupied.toggle(Square::from_coords(to.file(), from.rank()));
+    }
+
+    let mut stm = pos.turn();
+    let mut attackers = attackers_to(pos, to, occupied);
+    let mut res = 1;
+
+    loop {
+        stm = !stm;
+        attackers &= occupied;
+
+        let stm_attackers = attackers & board.by_color(stm);
+        if stm_attackers.is_empty() {
+            break;
+        }
+
+        res ^= 1;
+
+        for role in ATTACKER_ORDER {
+            let Some(sq) = (stm_attackers & board.by_role(role)).first() else {
+                continue;
+            };
+
+            /* A king can only capture if the square is no longer defended */
+            if role == Role::King {
+                return match (attackers & board.by_color(!stm)).any() {
+                    true => res ^ 1 == 1,
+                    false => res == 1,
+                };
+            }
+
+            swap = value(role) - swap;
+            if swap < res {
+                return res == 1;
+            }
+
+            /* Removing the attacker may reveal a slider behind it */
+            occupied.toggle(sq);
+            attackers |= attackers_to(pos, to, occupied);
+            break;
+        }
+    }
+
+    res == 1
+}
+
+/// Returns the pieces of both colors attacking a square with the given occupancy.
+fn attackers_to(pos: &Chess, sq: Square, occupied: Bitboard) -> Bitboard {
+    let board = pos.board();
+
+    board.attacks_to(sq, Color::White, occupied) | board.attacks_to(sq, Color::Black, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::uci::UciMove;
+    use shakmaty::CastlingMode;
+
+    /// Checks that the exchange of a move is worth exactly `expected`.
+    fn assert_see(fen: &str, uci: &str, expected: i32) {
+        let pos: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        let m = uci.parse::<UciMove>().unwrap().to_move(&pos).unwrap();
+
+        assert!(
+            see(&pos, &m, expected),
+            "{} {} below {}",
+            fen,
+            uci,
+            expected
+        );
+        assert!(
+            !see(&pos, &m, expected + 1),
+            "{} {} above {}",
+            fen,
+            uci,
+            expected
+        );
+    }
+
+    #[test]
+    fn undefended_pawn() {
+        assert_see(
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            "e1e5",
+            100,
+        );
+    }
+
+    #[test]
+    fn knight_takes_defended_pawn() {
+        assert_see(
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "d3e5",
+            -200,
+        );
+    }
+
+    #[test]
+    fn pawn_trade() {
+        assert_see("4k3/8/2p5/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", 0);
+    }
+
+    #[test]
+    fn queen_moving_to_an_attacked_square() {
+        assert_see("4k3/8/2p5/8/8/8/8/3QK3 w - - 0 1", "d1d5", -900);
+    }
+
+    #[test]
+    fn en_passant() {
+        assert_see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100);
+    }
+}
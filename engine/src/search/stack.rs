@@ -0,0 +1,76 @@
+//! Per-ply search stack.
+//! Holds what a node needs to know about its ancestors and siblings, indexed
+//! by the distance from the root rather than from the start of the game.
+
+use super::position::Undo;
+use crate::score::MAX_PLY;
+use shakmaty::{Move, Piece, Square};
+use std::ops::{Index, IndexMut};
+
+/// Number of killer moves kept per ply.
+pub const KILLERS: usize = 2;
+
+/// State of the search at a given ply.
+#[derive(Clone, Default)]
+pub struct StackEntry {
+    /// Static evaluation of the node
+    pub eval: Option<i32>,
+    /// Move being searched from the node, `None` for a null move
+    pub played: Option<Move>,
+    /// Move left out of the search of the node
+    pub excluded: Option<Move>,
+    /// Quiet moves which caused a beta cut-off at this ply, most recent first
+    pub killers: [Option<Move>; KILLERS],
+    /// Number of double extensions on the path from the root to the node
+    pub double_extensions: u8,
+    /// Continuation history index of the move being searched: moved piece and target square
+    pub cont_hist: Option<(Piece, Square)>,
+    /// Position before the move being searched, restored once it is taken back
+    pub undo: Undo,
+}
+
+/// Fixed-size stack with one entry per ply, plus room for the lookahead of the deepest node.
+pub struct SearchStack {
+    entries: Vec<StackEntry>,
+}
+
+impl SearchStack {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![StackEntry::default(); MAX_PLY as usize + 2],
+        }
+    }
+
+    /// Resets every entry, killers included.
+    pub fn clear(&mut self) {
+        self.entries.fill(StackEntry::default());
+    }
+
+    /// Stores a killer move, shifting the previous ones.
+    ///
+    /// # Arguments
+    /// * `ply` - Ply of the node the cut-off happened at
+    /// * `m` - Quiet move which caused the cut-off
+    pub fn store_killer(&mut self, ply: usize, m: &Move) {
+        let killers = &mut self.entries[ply].killers;
+
+        if killers[0].as_ref() != Some(m) {
+            killers.rotate_right(1);
+            killers[0] = Some(m.clone());
+        }
+    }
+}
+
+impl Index<usize> for SearchStack {
+    type Output = StackEntry;
+
+    fn index(&self, ply: usize) -> &Self::Output {
+        &self.entries[ply]
+    }
+}
+
+impl IndexMut<usize> for SearchStack {
+    fn index_mut(&mut self, ply: usize) -> &mut Self::Output {
+        &mut self.entries[ply]
+    }
+}
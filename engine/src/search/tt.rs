@@ -1,126 +1,582 @@
 //! Transposition table module for chess engine.
 //! Implements a hash table to store and retrieve previously evaluated positions.
+//! The table is shared between search threads and accessed lock-free.
+//! Entries are grouped in clusters filling one cache line, a position may be
+//! stored in any slot of the cluster its key maps to.
+//!
+//! An entry takes 10 bytes: a 16-bit key fragment and 64 bits of data packing
+//! the move, score, static eval, depth, bound and generation. The key fragment
+//! is xored with a fold of the data so that most torn writes are detected on
+//! probe, the remaining ones are caught by checking the move for legality.
+//!
+//! The table memory is allocated aligned to huge pages and zeroed by several
+//! threads, so that large tables are quick to set up.
 
 use crate::bound::Bound;
-use crate::moves::DEFAULT_MOVE;
+use crate::score;
 use shakmaty::zobrist::Zobrist64;
-use shakmaty::Move;
+use std::alloc::{self, Layout};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::atomic::{AtomicU16, AtomicU64, AtomicU8, Ordering};
+use std::thread;
+
+/// Number of distinct generations, the generation is stored on 6 bits.
+const GENERATION_CYCLE: u8 = 64;
+/// Number of slots in a cluster, 6 entries of 10 bytes fit a 64 bytes cache line.
+const CLUSTER_SIZE: usize = 6;
+/// Depth an entry loses in the replacement policy for each generation it is old.
+const AGE_WEIGHT: i32 = 8;
+/// Depth bonus of exact entries in the replacement policy.
+const EXACT_BONUS: i32 = 2;
+/// A same position entry from the current search is only overwritten by a
+/// shallower inexact result if it is at most this much deeper.
+const SAME_KEY_DEPTH_MARGIN: u8 = 4;
+/// Number of entries sampled to estimate `hashfull`.
+const HASHFULL_SAMPLE: usize = 1000;
+/// Magic bytes starting a saved table.
+const FILE_MAGIC: &[u8; 8] = b"PLUTOTT\0";
+/// Version of the saved table format, to bump whenever the entry layout changes.
+const FILE_VERSION: u32 = 1;
+/// Size of the header of a saved table: magic, version, size, length and generation.
+const FILE_HEADER_SIZE: u64 = 8 + 4 + 8 + 8 + 1;
+/// Size of a saved entry: key fragment and data.
+const FILE_ENTRY_SIZE: usize = 2 + 8;
+/// Alignment of tables of at least this size, the size of a huge page on Linux.
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+/// Largest table size in megabytes.
+#[cfg(target_pointer_width = "64")]
+pub const MAX_SIZE_MB: usize = 65536;
+/// Largest table size in megabytes, half of a 32-bit address space (wasm32).
+#[cfg(not(target_pointer_width = "64"))]
+pub const MAX_SIZE_MB: usize = 2048;
 
 /// Entry in the transposition table storing information about a previously evaluated position.
-#[repr(C, align(8))]
 pub struct TranspositionTableEntry {
-    /// Zobrist hash key of the position
-    pub key: Zobrist64,
     /// Evaluation score of the position
     pub score: i32,
+    /// Static evaluation of the position
+    pub eval: i32,
     /// Depth at which the position was evaluated
     pub depth: u8,
     /// Generation number to track entry age
     pub generation: u8,
     /// Type of score bound (exact, alpha, or beta)
     pub bound: Bound,
-    /// Best move found at this position
-    pub _move: Move,
+    /// Best move found at this position, packed with `moves::pack_move`
+    pub _move: u16,
+}
+
+/// Group of entries sharing one cache line.
+/// `keys[i]` holds the key fragment of entry `i` xored with the fold of `data[i]`.
+/// A cluster of zeroed memory is a valid cluster of empty entries.
+#[repr(C, align(64))]
+struct TranspositionTableCluster {
+    keys: [AtomicU16; CLUSTER_SIZE],
+    data: [AtomicU64; CLUSTER_SIZE],
 }
 
 /// Hash table storing evaluated chess positions for move ordering and pruning.
 pub struct TranspositionTable {
-    /// Vector of table entries
-    pub table: Vec<TranspositionTableEntry>,
+    /// Table clusters
+    table: ClusterMemory,
     /// Current generation number
-    pub generation: u8,
-    /// Size of the table
+    generation: AtomicU8,
+    /// Number of clusters in the table
     length: usize,
+    /// Size of the table in megabytes
+    size_mb: usize,
 }
 
 impl TranspositionTable {
-    /// Creates a new transposition table with specified size.
+    /// Creates a new transposition table using the given amount of memory.
     ///
     /// # Arguments
-    /// * `length` - Number of entries in the table
+    /// * `size_mb` - Size of the table in megabytes, capped to `MAX_SIZE_MB`
+    /// * `threads` - Number of threads zeroing the memory
     ///
     /// # Returns
     /// * New TranspositionTable instance
-    pub(crate) fn new(length: usize) -> TranspositionTable {
+    pub(crate) fn new(size_mb: usize, threads: usize) -> TranspositionTable {
+        let size_mb = size_mb.min(MAX_SIZE_MB);
+        let bytes = size_mb
+            .checked_mul(1024 * 1024)
+            .expect("invalid table size");
+        let length = (bytes / size_of::<TranspositionTableCluster>()).max(1);
+
         TranspositionTable {
-            table: vec![TranspositionTableEntry::default(); length],
-            generation: 0,
+            table: ClusterMemory::new(length, threads),
+            generation: AtomicU8::new(0),
             length,
+            size_mb,
+        }
+    }
+
+    /// Writes the table to a file, preceded by a header recording the format
+    /// version, the size of the table and its generation.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file to create
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(FILE_MAGIC)?;
+        file.write_all(&FILE_VERSION.to_le_bytes())?;
+        file.write_all(&(self.size_mb as u64).to_le_bytes())?;
+        file.write_all(&(self.length as u64).to_le_bytes())?;
+        file.write_all(&[self.generation()])?;
+
+        for cluster in self.table.iter() {
+            for i in 0..CLUSTER_SIZE {
+                file.write_all(&cluster.keys[i].load(Ordering::Relaxed).to_le_bytes())?;
+                file.write_all(&cluster.data[i].load(Ordering::Relaxed).to_le_bytes())?;
+            }
+        }
+
+        file.flush()
+    }
+
+    /// Reads a table written by `save`.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file to read
+    ///
+    /// # Returns
+    /// * The table with the size recorded in the file, or an error if the file
+    ///   is not a saved table of the current format
+    pub fn load(path: &str) -> io::Result<TranspositionTable> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason);
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid("not a hash file"));
+        }
+
+        let mut word = [0; 4];
+        file.read_exact(&mut word)?;
+        if u32::from_le_bytes(word) != FILE_VERSION {
+            return Err(invalid("unsupported hash file version"));
+        }
+
+        let mut dword = [0; 8];
+        file.read_exact(&mut dword)?;
+        let size_mb = u64::from_le_bytes(dword);
+        if size_mb == 0 || size_mb > MAX_SIZE_MB as u64 {
+            return Err(invalid("unsupported hash file size"));
+        }
+
+        /* Check the header against the file before allocating the table it describes */
+        file.read_exact(&mut dword)?;
+        let length = u64::from_le_bytes(dword);
+        let expected_len = length
+            .checked_mul((CLUSTER_SIZE * FILE_ENTRY_SIZE) as u64)
+            .and_then(|len| len.checked_add(FILE_HEADER_SIZE));
+        if expected_len != Some(file_len) {
+            return Err(invalid("hash file size does not match its header"));
+        }
+
+        let table = TranspositionTable::new(size_mb as usize, 1);
+        if length != table.length as u64 {
+            return Err(invalid("hash file size does not match its header"));
+        }
+
+        let mut byte = [0; 1];
+        file.read_exact(&mut byte)?;
+        /* Step back one generation, the next search then continues the saved one */
+        let generation = (byte[0] % GENERATION_CYCLE + GENERATION_CYCLE - 1) % GENERATION_CYCLE;
+        table.generation.store(generation, Ordering::Relaxed);
+
+        let mut half = [0; 2];
+        for cluster in table.table.iter() {
+            for i in 0..CLUSTER_SIZE {
+                file.read_exact(&mut half)?;
+                file.read_exact(&mut dword)?;
+                cluster.keys[i].store(u16::from_le_bytes(half), Ordering::Relaxed);
+                cluster.data[i].store(u64::from_le_bytes(dword), Ordering::Relaxed);
+            }
         }
+
+        Ok(table)
+    }
+
+    /// Returns the size of the table in megabytes.
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    /// Returns the cluster a key maps to.
+    fn cluster(&self, key: Zobrist64) -> &TranspositionTableCluster {
+        &self.table[key.0 as usize % self.length]
     }
 
     /// Probes the table for a position.
     ///
     /// # Arguments
     /// * `key` - Zobrist hash of the position to look up
+    /// * `ply` - Distance from the root, mate scores are made root-relative again
     ///
     /// # Returns
-    /// * Copy of the table entry at the corresponding index
-    pub fn probe(&self, key: Zobrist64) -> TranspositionTableEntry {
-        let index = key.0 as usize % self.length;
-        self.table[index].clone()
+    /// * Copy of the entry of the position, or `None` if no slot of the
+    ///   cluster holds the position
+    pub fn probe(&self, key: Zobrist64, ply: usize) -> Option<TranspositionTableEntry> {
+        let cluster = self.cluster(key);
+        let fragment = key_fragment(key);
+
+        for i in 0..CLUSTER_SIZE {
+            let data = cluster.data[i].load(Ordering::Relaxed);
+
+            if cluster.keys[i].load(Ordering::Relaxed) ^ fold(data) == fragment {
+                if let Some(mut entry) = TranspositionTableEntry::unpack(data) {
+                    entry.score = score::from_tt(entry.score, ply);
+
+                    return Some(entry);
+                }
+            }
+        }
+
+        None
     }
 
-    /// Stores a position in the table.
+    /// Stores a position in the table. An entry of the same position is
+    /// updated in place, otherwise the least valuable slot of the cluster is
+    /// replaced, weighing depth, bound and age.
     ///
     /// # Arguments
     /// * `key` - Zobrist hash of the position
     /// * `depth` - Search depth at which position was evaluated
     /// * `score` - Evaluation score
+    /// * `eval` - Static evaluation of the position
     /// * `bound` - Type of score bound
-    /// * `_move` - Best move found at this position
-    pub fn store(&mut self, key: Zobrist64, depth: u8, score: i32, bound: Bound, _move: Move) {
-        let index = key.0 as usize % self.table.len();
+    /// * `_move` - Best move found at this position, packed with `moves::pack_move`
+    /// * `ply` - Distance from the root, mate scores are stored relative to the position
+    #[allow(clippy::too_many_arguments)]
+    pub fn store(
+        &self,
+        key: Zobrist64,
+        depth: u8,
+        score: i32,
+        eval: i32,
+        bound: Bound,
+        _move: u16,
+        ply: usize,
+    ) {
+        let cluster = self.cluster(key);
+        let fragment = key_fragment(key);
+        let generation = self.generation();
+        let mut replace = 0;
+        let mut replace_value = i32::MAX;
+
+        for i in 0..CLUSTER_SIZE {
+            let data = cluster.data[i].load(Ordering::Relaxed);
+            let old = match TranspositionTableEntry::unpack(data) {
+                Some(old) => old,
+                None => {
+                    /* Empty slot */
+                    replace = i;
+                    break;
+                }
+            };
+
+            if cluster.keys[i].load(Ordering::Relaxed) ^ fold(data) == fragment {
+                /* Keep a much deeper result of the current search */
+                if bound != Bound::Exact
+                    && old.generation == generation
+                    && old.depth > depth.saturating_add(SAME_KEY_DEPTH_MARGIN)
+                {
+                    return;
+                }
+
+                replace = i;
+                break;
+            }
+
+            let value = old.replacement_value(generation);
+
+            if value < replace_value {
+                replace = i;
+                replace_value = value;
+            }
+        }
+
         let entry = TranspositionTableEntry {
-            key,
             depth,
-            score,
+            score: score::to_tt(score, ply),
+            eval,
             bound,
             _move,
-            generation: self.generation,
+            generation,
         };
+        let data = entry.pack();
+
+        cluster.keys[replace].store(fragment ^ fold(data), Ordering::Relaxed);
+        cluster.data[replace].store(data, Ordering::Relaxed);
+    }
 
-        self.table[index] = entry;
+    /// Returns the current generation number.
+    pub fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
     }
 
     /// Increments the generation counter at the start of a new search.
-    pub fn new_search(&mut self) {
-        self.generation += 1;
+    pub fn new_search(&self) {
+        let generation = (self.generation() + 1) % GENERATION_CYCLE;
+
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    /// Estimates how full the table is from a sample of its first entries.
+    ///
+    /// # Returns
+    /// * Per mille of the sampled entries written during the current search
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation();
+        let clusters = self.length.min(HASHFULL_SAMPLE.div_ceil(CLUSTER_SIZE));
+        let used = self.table[..clusters]
+            .iter()
+            .flat_map(|cluster| cluster.data.iter())
+            .filter_map(|data| TranspositionTableEntry::unpack(data.load(Ordering::Relaxed)))
+            .filter(|entry| entry.generation == generation)
+            .count();
+
+        used * 1000 / (clusters * CLUSTER_SIZE)
     }
 
     /// Clears all entries in the table.
-    #[allow(dead_code)]
-    pub fn clear(&mut self) {
-        self.table = vec![TranspositionTableEntry::default(); self.length];
+    pub fn clear(&self) {
+        for cluster in self.table.iter() {
+            for i in 0..CLUSTER_SIZE {
+                cluster.keys[i].store(0, Ordering::Relaxed);
+                cluster.data[i].store(0, Ordering::Relaxed);
+            }
+        }
     }
 }
 
-/// Implements cloning for table entries.
-impl Clone for TranspositionTableEntry {
-    /// Creates an exact copy of a table entry.
-    fn clone(&self) -> Self {
-        TranspositionTableEntry {
-            key: self.key,
-            depth: self.depth,
-            score: self.score,
-            generation: self.generation,
-            bound: self.bound.clone(),
-            _move: self._move.clone(),
+/// Memory holding the clusters of a table, allocated with an explicit
+/// alignment so that large tables start on a huge page boundary.
+struct ClusterMemory {
+    ptr: NonNull<TranspositionTableCluster>,
+    length: usize,
+    layout: Layout,
+}
+
+/* The clusters only contain atomics */
+unsafe impl Send for ClusterMemory {}
+unsafe impl Sync for ClusterMemory {}
+
+impl ClusterMemory {
+    /// Allocates zeroed memory for `length` clusters. On Linux the kernel is
+    /// advised to back it with transparent huge pages before it is touched.
+    ///
+    /// # Arguments
+    /// * `length` - Number of clusters, at least 1
+    /// * `threads` - Number of threads zeroing the memory
+    fn new(length: usize, threads: usize) -> Self {
+        let size = length * size_of::<TranspositionTableCluster>();
+        let align = match size >= HUGE_PAGE_SIZE {
+            true => HUGE_PAGE_SIZE,
+            false => align_of::<TranspositionTableCluster>(),
+        };
+        let layout = Layout::from_size_align(size, align).expect("invalid table size");
+        let ptr = unsafe { alloc::alloc(layout) };
+
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(ptr as *mut libc::c_void, size, libc::MADV_HUGEPAGE);
+        }
+
+        zero(ptr, size, threads);
+
+        ClusterMemory {
+            ptr: NonNull::new(ptr as *mut TranspositionTableCluster).unwrap(),
+            length,
+            layout,
+        }
+    }
+}
+
+impl Deref for ClusterMemory {
+    type Target = [TranspositionTableCluster];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.length) }
+    }
+}
+
+impl Drop for ClusterMemory {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, self.layout) }
+    }
+}
+
+/// Zeroes freshly allocated memory, splitting it between threads.
+///
+/// # Arguments
+/// * `start` - Start of the memory
+/// * `size` - Size of the memory in bytes
+/// * `threads` - Number of threads to use, the browser build always uses one
+fn zero(start: *mut u8, size: usize, threads: usize) {
+    let memory = unsafe { slice::from_raw_parts_mut(start as *mut MaybeUninit<u8>, size) };
+
+    #[cfg(target_arch = "wasm32")]
+    let threads = 1;
+
+    if threads <= 1 {
+        unsafe { ptr::write_bytes(memory.as_mut_ptr(), 0, size) };
+        return;
+    }
+
+    thread::scope(|scope| {
+        for part in memory.chunks_mut(size.div_ceil(threads)) {
+            scope.spawn(|| unsafe { ptr::write_bytes(part.as_mut_ptr(), 0, part.len()) });
         }
+    });
+}
+
+/// Returns the part of the key stored in the entry, the low bits already
+/// select the cluster.
+fn key_fragment(key: Zobrist64) -> u16 {
+    (key.0 >> 48) as u16
+}
+
+/// Saturates a score to the 16 bits it is stored on.
+fn to_i16(score: i32) -> i16 {
+    score.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Folds the packed data of an entry into 16 bits.
+fn fold(data: u64) -> u16 {
+    (data ^ data >> 16 ^ data >> 32 ^ data >> 48) as u16
+}
+
+impl TranspositionTableEntry {
+    /// Packs every field into 64 bits: move (16), score (16), eval (16),
+    /// depth (8), generation (6) and bound (2). The bound is stored off by
+    /// one so that an empty slot reads as 0.
+    fn pack(&self) -> u64 {
+        self._move as u64
+            | (to_i16(self.score) as u16 as u64) << 16
+            | (to_i16(self.eval) as u16 as u64) << 32
+            | (self.depth as u64) << 48
+            | (self.generation as u64) << 56
+            | (self.bound.clone() as u64 + 1) << 62
+    }
+
+    /// Rebuilds an entry from its packed data.
+    ///
+    /// # Returns
+    /// * The entry, or `None` if the data comes from an empty slot
+    fn unpack(data: u64) -> Option<TranspositionTableEntry> {
+        let bound = match data >> 62 {
+            0 => return None,
+            1 => Bound::Exact,
+            2 => Bound::Beta,
+            _ => Bound::Alpha,
+        };
+
+        Some(TranspositionTableEntry {
+            _move: data as u16,
+            score: (data >> 16) as u16 as i16 as i32,
+            eval: (data >> 32) as u16 as i16 as i32,
+            depth: (data >> 48) as u8,
+            generation: (data >> 56) as u8 % GENERATION_CYCLE,
+            bound,
+        })
+    }
+
+    /// Returns how valuable the entry is to keep, deep and exact entries of
+    /// recent searches are replaced last.
+    ///
+    /// # Arguments
+    /// * `generation` - Current generation of the table
+    fn replacement_value(&self, generation: u8) -> i32 {
+        let age = (GENERATION_CYCLE + generation - self.generation) % GENERATION_CYCLE;
+        let bonus = match self.bound {
+            Bound::Exact => EXACT_BONUS,
+            _ => 0,
+        };
+
+        self.depth as i32 + bonus - AGE_WEIGHT * age as i32
     }
 }
 
-/// Provides default values for table entries.
-impl Default for TranspositionTableEntry {
-    /// Creates a new entry with default values.
-    fn default() -> Self {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::MATE;
+
+    fn entry(
+        score: i32,
+        eval: i32,
+        depth: u8,
+        generation: u8,
+        bound: Bound,
+    ) -> TranspositionTableEntry {
         TranspositionTableEntry {
-            key: Zobrist64(0),
-            depth: 0,
-            score: 0,
-            generation: 0,
-            bound: Bound::Alpha,
-            _move: DEFAULT_MOVE.clone(),
+            score,
+            eval,
+            depth,
+            generation,
+            bound,
+            _move: 0b1010_0111_0001_1100,
+        }
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        for bound in [Bound::Exact, Bound::Beta, Bound::Alpha] {
+            let original = entry(-MATE + 3, 417, 255, GENERATION_CYCLE - 1, bound);
+            let unpacked = TranspositionTableEntry::unpack(original.pack()).unwrap();
+
+            assert_eq!(unpacked.score, original.score);
+            assert_eq!(unpacked.eval, original.eval);
+            assert_eq!(unpacked.depth, original.depth);
+            assert_eq!(unpacked.generation, original.generation);
+            assert_eq!(unpacked.bound, original.bound);
+            assert_eq!(unpacked._move, original._move);
         }
     }
+
+    #[test]
+    fn empty_slot_unpacks_to_none() {
+        assert!(TranspositionTableEntry::unpack(0).is_none());
+    }
+
+    #[test]
+    fn probe_finds_stored_entry() {
+        let tt = TranspositionTable::new(1, 1);
+        let key = Zobrist64(0x1234_5678_9abc_def0);
+
+        tt.store(key, 6, 35, -12, Bound::Beta, 0x0421, 0);
+        let found = tt.probe(key, 0).unwrap();
+
+        assert_eq!(found.score, 35);
+        assert_eq!(found.eval, -12);
+        assert_eq!(found.depth, 6);
+        assert_eq!(found.bound, Bound::Beta);
+        assert_eq!(found._move, 0x0421);
+        assert!(tt.probe(Zobrist64(key.0 ^ 1 << 60), 0).is_none());
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_node() {
+        let tt = TranspositionTable::new(1, 1);
+        let key = Zobrist64(0x0fed_cba9_8765_4321);
+
+        tt.store(key, 4, MATE - 10, 0, Bound::Exact, 0, 3);
+
+        assert_eq!(tt.probe(key, 5).unwrap().score, MATE - 12);
+    }
 }
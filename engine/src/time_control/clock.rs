@@ -0,0 +1,31 @@
+/// Monotonic stopwatch used to measure the time spent searching.
+/// `std::time::Instant` is not available in the browser, so the wasm build
+/// falls back to the JavaScript wall clock through `chrono`.
+#[derive(Clone, Copy)]
+pub struct Clock {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start: i64,
+}
+
+impl Clock {
+    /// Starts a new stopwatch at the current time.
+    pub fn start() -> Self {
+        Clock {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    /// Returns the time elapsed since the stopwatch was started in milliseconds.
+    pub fn elapsed(&self) -> u128 {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed().as_millis();
+
+        #[cfg(target_arch = "wasm32")]
+        return (chrono::Utc::now().timestamp_millis() - self.start).max(0) as u128;
+    }
+}
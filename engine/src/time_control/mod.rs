@@ -1,2 +1,3 @@
-pub mod time_mode;
+pub mod clock;
 pub mod time_controller;
+pub mod time_mode;
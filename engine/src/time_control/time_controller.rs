@@ -1,23 +1,54 @@
+use crate::time_control::clock::Clock;
 use crate::time_control::time_mode::TimeMode;
 use crate::{config::Config, search::params::SearchParams};
-use chrono::Local;
-use shakmaty::{Chess, Color, Position};
+use shakmaty::{Chess, Color, Move, Position};
+
+/// Game phase of the starting position, see `TimeController::phase`
+const MAX_PHASE: u128 = 24;
+/// Number of iterations after which the best move counts as fully stable
+const MAX_STABILITY: u64 = 5;
+/// Largest score drop in centipawns that still extends the soft limit
+const MAX_SCORE_DROP: i64 = 100;
+/// Lower bound of every soft limit scale in percent
+const MIN_SCALE: u128 = 25;
+/// Number of nodes searched between two reads of the clock
+const TIME_CHECK_INTERVAL: u64 = 1024;
+/// Smallest budget in milliseconds, kept even when the overhead eats the whole clock
+const MIN_BUDGET: u128 = 10;
 
 /// Manages time control for chess engine operations.
 /// Handles different time modes and tracks elapsed time during search.
 pub struct TimeController {
     /// Current time control mode (e.g., Infinite, MoveTime, WOrBTime)
     pub time_mode: TimeMode,
-    /// Timestamp when search started
-    start_time: i64,
-    /// Allocated time for current search in milliseconds
-    pub play_time: u128,
+    /// Stopwatch started when the search started
+    clock: Clock,
+    /// Soft limit in milliseconds, no new iteration is started after it
+    pub soft_time: u128,
+    /// Hard limit in milliseconds, the search is aborted after it
+    pub hard_time: u128,
+    /// Soft limit before scaling by the state of the search
+    base_soft_time: u128,
+    /// Best move of the previous iteration
+    best_move: Option<Move>,
+    /// Number of consecutive iterations that kept the best move
+    stability: u64,
+    /// Score of the previous iteration
+    score: Option<i32>,
+    /// Pondering on the opponent's time, the clock is not running yet
+    pub pondering: bool,
+    /// Hard limit already exceeded, cached so the clock is not read again
+    stopped: bool,
+    /// Node count at which the clock is read next
+    next_check: u64,
 }
 
 impl TimeController {
-    /// Starts the time control by setting the start time to current system time.
+    /// Starts the time control by restarting the stopwatch.
     pub fn start(&mut self) {
-        self.start_time = Local::now().timestamp_millis();
+        self.clock = Clock::start();
+        self.stopped = false;
+        self.next_check = 0;
     }
 
     /// Configures time control based on search parameters and game state.
@@ -26,35 +57,177 @@ impl TimeController {
     /// * `params` - Search parameters containing time allocations
     /// * `game` - Current chess position for determining active player
     pub fn setup(&mut self, params: &SearchParams, game: &Chess, cfg: &Config) {
-        self.play_time = match self.time_mode {
-            TimeMode::MoveTime => params.move_time,
-            TimeMode::WOrBTime => match game.turn() {
-                Color::White => params.w_time / cfg.tc_time_divisor.value as u128,
-                Color::Black => params.b_time / cfg.tc_time_divisor.value as u128,
-            },
+        /* Time lost to communication and lag is never available to the search */
+        let overhead = cfg.move_overhead.value as u128;
+
+        (self.base_soft_time, self.hard_time) = match self.time_mode {
+            TimeMode::MoveTime => {
+                let time = params.move_time.saturating_sub(overhead).max(MIN_BUDGET);
+                (time, time)
+            }
+            TimeMode::WOrBTime => {
+                let (time, inc) = match game.turn() {
+                    Color::White => (params.w_time, params.w_inc),
+                    Color::Black => (params.b_time, params.b_inc),
+                };
+                let time = time.saturating_sub(overhead);
+                let soft = Self::allocate(time, inc, params.moves_to_go, game, cfg);
+                let hard = (soft * cfg.tc_hard_factor.value as u128 / 100)
+                    .min(time / cfg.tc_time_divisor.value as u128)
+                    .max(MIN_BUDGET);
+
+                (soft.clamp(MIN_BUDGET, hard), hard)
+            }
+            _ => (0, 0),
+        };
+        self.soft_time = self.base_soft_time;
+        self.best_move = None;
+        self.stability = 0;
+        self.score = None;
+        self.pondering = params.ponder;
+
+        self.start();
+    }
+
+    /// Computes the time budget for one move from the clock state.
+    ///
+    /// # Arguments
+    /// * `time` - Remaining time of the side to move in milliseconds
+    /// * `inc` - Increment per move in milliseconds
+    /// * `moves_to_go` - Moves until the next time control, if any
+    /// * `game` - Current chess position, used to estimate the game phase
+    ///
+    /// # Returns
+    /// * Soft limit in milliseconds
+    fn allocate(
+        time: u128,
+        inc: u128,
+        moves_to_go: Option<u32>,
+        game: &Chess,
+        cfg: &Config,
+    ) -> u128 {
+        /* Without movestogo, expect fewer moves left as the pieces come off */
+        let moves_left = match moves_to_go {
+            Some(moves_to_go) => (moves_to_go as u128).max(1),
+            None => {
+                let min = cfg.tc_moves_left_min.value as u128;
+                let max = (cfg.tc_moves_left_max.value as u128).max(min);
+                min + (max - min) * Self::phase(game) / MAX_PHASE
+            }
+        };
+
+        time / moves_left + inc * cfg.tc_inc_factor.value as u128 / 100
+    }
+
+    /// Returns the game phase from the non-pawn material on the board,
+    /// `MAX_PHASE` for the starting position down to 0 for a pawn ending.
+    fn phase(game: &Chess) -> u128 {
+        let board = game.board();
+        let phase = board.knights().count()
+            + board.bishops().count()
+            + 2 * board.rooks().count()
+            + 4 * board.queens().count();
+
+        (phase as u128).min(MAX_PHASE)
+    }
+
+    /// Rescales the soft limit after a completed iteration. The soft limit
+    /// shrinks while the best move stays the same and takes most of the nodes,
+    /// and grows when the best move changes or the score drops.
+    ///
+    /// # Arguments
+    /// * `best_move` - Best move of the completed iteration
+    /// * `score` - Score of the best move
+    /// * `best_move_nodes` - Nodes spent on the subtree of the best move
+    /// * `nodes` - Nodes spent on the whole iteration
+    pub fn update(
+        &mut self,
+        best_move: &Move,
+        score: i32,
+        best_move_nodes: u64,
+        nodes: u64,
+        cfg: &Config,
+    ) {
+        if self.time_mode != TimeMode::WOrBTime {
+            return;
+        }
+
+        self.stability = match &self.best_move {
+            Some(m) if m == best_move => (self.stability + 1).min(MAX_STABILITY),
             _ => 0,
         };
+        self.best_move = Some(best_move.clone());
+
+        let stability_scale =
+            cfg.tc_stability_base
+                .value
+                .saturating_sub(cfg.tc_stability_step.value * self.stability) as u128;
+
+        let score_drop = match self.score {
+            Some(prev) => (prev as i64 - score as i64).clamp(0, MAX_SCORE_DROP),
+            None => 0,
+        };
+        let score_scale = (100 + score_drop * cfg.tc_score_drop_factor.value / 100) as u128;
+        self.score = Some(score);
+
+        let best_move_share = (best_move_nodes * 100 / nodes.max(1)) as u128;
+        let nodes_scale = (cfg.tc_nodes_base.value as u128)
+            .saturating_sub(best_move_share * cfg.tc_nodes_factor.value as u128 / 100);
+
+        self.soft_time = (self.base_soft_time
+            * stability_scale.max(MIN_SCALE)
+            * score_scale.max(MIN_SCALE)
+            * nodes_scale.max(MIN_SCALE)
+            / 1_000_000)
+            .min(self.hard_time);
+    }
 
+    /// Checks if the soft limit has passed, so that no new iteration should be started.
+    /// An iteration is not started either when it is not expected to finish
+    /// before the hard limit, taking `TCElapsedFactor` times the time spent so far.
+    ///
+    /// # Returns
+    /// * `true` if the soft limit is exceeded, `false` otherwise or if in infinite time mode
+    pub fn is_soft_time_up(&self, cfg: &Config) -> bool {
+        let elapsed = self.elapsed();
+
+        TimeMode::is_finite(&self.time_mode)
+            && (elapsed > self.soft_time
+                || elapsed * cfg.tc_elapsed_factor.value as u128 > self.hard_time)
+    }
+
+    /// Switches a ponder search to the normal time budget, counted from now.
+    pub fn ponderhit(&mut self) {
+        self.pondering = false;
         self.start();
     }
 
     /// Returns elapsed time since search start in milliseconds.
-    pub fn elapsed(&self) -> i64 {
-        Local::now().timestamp_millis() - self.start_time
+    pub fn elapsed(&self) -> u128 {
+        self.clock.elapsed()
     }
 
-    /// Checks if allocated time for current search has been exhausted.
+    /// Checks if the hard limit of the current search has been exhausted.
+    /// The clock is only read every `TIME_CHECK_INTERVAL` nodes.
+    ///
+    /// # Arguments
+    /// * `nodes` - Number of nodes searched so far
     ///
     /// # Returns
     /// * `true` if time is up, `false` otherwise or if in infinite time mode
-    pub fn is_time_up(&self) -> bool {
-        if !TimeMode::is_finite(&self.time_mode) {
+    pub fn is_time_up(&mut self, nodes: u64) -> bool {
+        if self.stopped {
+            return true;
+        }
+
+        if !TimeMode::is_finite(&self.time_mode) || nodes < self.next_check {
             return false;
         }
 
-        let elapsed = Local::now().timestamp_millis() - self.start_time;
+        self.next_check = nodes + TIME_CHECK_INTERVAL;
+        self.stopped = self.elapsed() > self.hard_time;
 
-        elapsed as u128 > self.play_time
+        self.stopped
     }
 }
 
@@ -62,9 +235,17 @@ impl TimeController {
 impl Default for TimeController {
     fn default() -> Self {
         TimeController {
-            start_time: Local::now().timestamp_millis(),
+            clock: Clock::start(),
             time_mode: TimeMode::Infinite,
-            play_time: 0,
+            soft_time: 0,
+            hard_time: 0,
+            base_soft_time: 0,
+            best_move: None,
+            stability: 0,
+            score: None,
+            pondering: false,
+            stopped: false,
+            next_check: 0,
         }
     }
 }
@@ -46,4 +46,4 @@ impl PartialEq for TimeMode {
             _ => false,
         }
     }
-}
\ No newline at end of file
+}
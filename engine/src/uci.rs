@@ -7,29 +7,112 @@ use crate::nnue::NNUEState;
 use crate::postMessage;
 use crate::search::search::Search;
 use crate::search::tt::TranspositionTable;
+use crate::time_control::clock::Clock;
 use crate::time_control::time_mode::TimeMode;
-use chrono::Local;
 use queues::{queue, IsQueue, Queue};
 use shakmaty::fen::Fen;
 use shakmaty::uci::UciMove;
 use shakmaty::zobrist::ZobristHash;
 use shakmaty::{CastlingMode, Chess, Position};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::{self, JoinHandle};
 
 /// Main UCI protocol handler implementing the Universal Chess Interface.
 pub struct UciController {
-    search: Search,
+    /// Search instance, `None` while it is owned by the search thread
+    search: Option<Search>,
+    /// Handle of the running search thread, gives the search back once joined
+    #[cfg(not(target_arch = "wasm32"))]
+    worker: Option<JoinHandle<Search>>,
+    /// Stop flag shared with the search
+    stop: Arc<AtomicBool>,
+    /// Ponder flag shared with the search, cleared on `ponderhit`
+    ponder: Arc<AtomicBool>,
 }
 
 impl Default for UciController {
     /// Creates a new UCI instance with default settings.
     fn default() -> UciController {
+        let search = Search::new();
+
         UciController {
-            search: Search::new(),
+            stop: Arc::clone(&search.state.stop),
+            ponder: Arc::clone(&search.state.ponder),
+            search: Some(search),
+            #[cfg(not(target_arch = "wasm32"))]
+            worker: None,
         }
     }
 }
 
+impl Drop for UciController {
+    /// Waits for a running search so that its `bestmove` is not lost.
+    fn drop(&mut self) {
+        self.join_search();
+    }
+}
+
 impl UciController {
+    /// Returns the search instance, stopping the search thread if one is running.
+    /// A command needing the search ends the running one as a `stop` would, an
+    /// infinite or ponder search would otherwise keep the thread reading the
+    /// commands blocked.
+    fn search(&mut self) -> &mut Search {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.worker.is_some() {
+            /* A ponder search is released before being stopped, as the opponent
+             * did not play the expected move */
+            self.ponder.store(false, Ordering::Relaxed);
+            self.handle_stop();
+        }
+
+        self.join_search()
+    }
+
+    /// Returns the search instance, waiting for the search thread to finish if one is running.
+    fn join_search(&mut self) -> &mut Search {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(worker) = self.worker.take() {
+            self.search = Some(worker.join().expect("Search thread panicked"));
+        }
+
+        self.search.as_mut().unwrap()
+    }
+
+    /// Starts the search on a dedicated thread so that `stop`, `isready` and
+    /// `quit` are still processed while it runs.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_search(&mut self) {
+        self.search();
+
+        let mut search = self.search.take().unwrap();
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder
+            .store(search.state.params.ponder, Ordering::Relaxed);
+        self.worker = Some(
+            thread::Builder::new()
+                .stack_size(8 * 1024 * 1024) // 8MB stack size
+                .spawn(move || {
+                    search.go(true);
+                    search
+                })
+                .expect("Thread creation failed"),
+        );
+    }
+
+    /// Runs the search on the current thread, the web worker has no threads to spare.
+    #[cfg(target_arch = "wasm32")]
+    fn start_search(&mut self) {
+        let ponder = self.search().state.params.ponder;
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder.store(ponder, Ordering::Relaxed);
+        self.search().go(true);
+    }
+
     /// Parses a UCI command string and processes it.
     ///
     /// # Arguments
@@ -52,9 +135,6 @@ impl UciController {
     fn parse_tokens(&mut self, tokens: &mut Queue<&str>) {
         let first_token = tokens.remove().unwrap();
 
-        self.search.state.tc.time_mode = TimeMode::Infinite;
-        self.search.state.params.depth = u8::MAX;
-
         match first_token {
             "print" => self.handle_print(tokens),
             "bench" => self.handle_bench(),
@@ -64,12 +144,19 @@ impl UciController {
             "setoption" => self.handle_setoption(tokens),
             "ucinewgame" => self.handle_ucinewgame(),
             "position" => self.handle_position(tokens),
-            "go" => self.handle_go(tokens),
+            "go" => {
+                self.reset_go_params();
+                self.handle_go(tokens)
+            }
+            "stop" => self.handle_stop(),
+            "ponderhit" => self.handle_ponderhit(),
+            "save" => self.handle_save(tokens),
+            "load" => self.handle_load(tokens),
             _ => Logger::log(&format!("Unknown command: {}", first_token)),
         }
     }
 
-    fn handle_print(&self, tokens: &mut Queue<&str>) {
+    fn handle_print(&mut self, tokens: &mut Queue<&str>) {
         let scope = tokens.remove().unwrap();
 
         match scope {
@@ -78,7 +165,60 @@ impl UciController {
         }
     }
 
-    fn handle_print_spsa(&self, tokens: &mut Queue<&str>) {
+    fn handle_save(&mut self, tokens: &mut Queue<&str>) {
+        let target = tokens.remove().unwrap_or("");
+
+        match target {
+            "hash" => self.handle_save_hash(),
+            _ => Logger::log(&format!("unknown target: {}", target)),
+        }
+    }
+
+    fn handle_load(&mut self, tokens: &mut Queue<&str>) {
+        let target = tokens.remove().unwrap_or("");
+
+        match target {
+            "hash" => self.handle_load_hash(),
+            _ => Logger::log(&format!("unknown target: {}", target)),
+        }
+    }
+
+    /// Writes the transposition table to the file set with the `HashFile` option.
+    fn handle_save_hash(&mut self) {
+        let path = self.search().state.cfg.hash_file.value.clone();
+
+        if path.is_empty() {
+            Logger::log("info string HashFile is not set");
+            return;
+        }
+
+        match self.search().state.tt.save(&path) {
+            Ok(()) => Logger::log(&format!("info string hash saved to {}", path)),
+            Err(e) => Logger::log(&format!("info string could not save hash: {}", e)),
+        }
+    }
+
+    /// Replaces the transposition table with the one saved in the file set with
+    /// the `HashFile` option, `Hash` takes the size of the saved table.
+    fn handle_load_hash(&mut self) {
+        let path = self.search().state.cfg.hash_file.value.clone();
+
+        if path.is_empty() {
+            Logger::log("info string HashFile is not set");
+            return;
+        }
+
+        match TranspositionTable::load(&path) {
+            Ok(tt) => {
+                self.search().state.cfg.hash.value = tt.size_mb();
+                self.search().state.tt = Arc::new(tt);
+                Logger::log(&format!("info string hash loaded from {}", path));
+            }
+            Err(e) => Logger::log(&format!("info string could not load hash: {}", e)),
+        }
+    }
+
+    fn handle_print_spsa(&mut self, tokens: &mut Queue<&str>) {
         let target = tokens.remove().unwrap();
 
         match target {
@@ -87,35 +227,56 @@ impl UciController {
         }
     }
 
-    fn handle_print_spsa_workload(&self) {
-        Logger::log(&self.search.state.cfg.qsearch_depth.fmt_spsa());
-        Logger::log(&self.search.state.cfg.rfp_depth.fmt_spsa());
-        Logger::log(&self.search.state.cfg.rfp_base_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.rfp_reduction_improving.fmt_spsa());
-        Logger::log(&self.search.state.cfg.fp_base_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.fp_depth_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.fp_margin_depth_factor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.nmp_depth.fmt_spsa());
-        Logger::log(&self.search.state.cfg.nmp_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.nmp_divisor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.nmp_divisor_improving.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmp_move_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmp_depth_factor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_depth.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_move_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_quiet_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_quiet_divisor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_base_margin.fmt_spsa());
-        Logger::log(&self.search.state.cfg.lmr_base_divisor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.mo_tt_entry_value.fmt_spsa());
-        Logger::log(&self.search.state.cfg.mo_capture_value.fmt_spsa());
-        Logger::log(&self.search.state.cfg.mo_killer_value.fmt_spsa());
-        Logger::log(&self.search.state.cfg.tc_time_divisor.fmt_spsa());
-        Logger::log(&self.search.state.cfg.tc_elapsed_factor.fmt_spsa());
+    fn handle_print_spsa_workload(&mut self) {
+        Logger::log(&self.search().state.cfg.qsearch_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.rfp_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.rfp_base_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.rfp_reduction_improving.fmt_spsa());
+        Logger::log(&self.search().state.cfg.fp_base_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.fp_depth_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.fp_margin_depth_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.nmp_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.nmp_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.nmp_divisor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.nmp_divisor_improving.fmt_spsa());
+        Logger::log(&self.search().state.cfg.nmp_verification_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmp_move_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmp_depth_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.see_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.see_capture_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.see_quiet_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.se_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.se_tt_depth_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.se_beta_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.se_double_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.se_double_limit.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_move_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_quiet_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_quiet_divisor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_base_margin.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_base_divisor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.lmr_history_divisor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.mo_capture_value.fmt_spsa());
+        Logger::log(&self.search().state.cfg.mo_killer_value.fmt_spsa());
+        Logger::log(&self.search().state.cfg.asp_depth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.asp_delta.fmt_spsa());
+        Logger::log(&self.search().state.cfg.asp_growth.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_time_divisor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_moves_left_min.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_moves_left_max.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_inc_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_hard_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_stability_base.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_stability_step.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_score_drop_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_nodes_base.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_nodes_factor.fmt_spsa());
+        Logger::log(&self.search().state.cfg.tc_elapsed_factor.fmt_spsa());
     }
 
     fn handle_bench(&mut self) {
-        self.search.state.tt.clear();
+        self.search().state.tt.clear();
 
         let positions = vec![
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ",
@@ -126,29 +287,45 @@ impl UciController {
         ];
 
         let mut total = 0;
-        let start_time = Local::now().timestamp_millis();
+        let clock = Clock::start();
 
         for position in positions {
             let fen: Fen = position.parse().ok().unwrap();
             let game = fen.into_position(CastlingMode::Standard).ok().unwrap();
 
-            self.search.state.game = game;
-            self.search.state.params.depth = 14;
-            self.search.state.tc.time_mode = TimeMode::Infinite;
+            /* Limits of a previous go must not apply to the bench */
+            self.reset_go_params();
+            self.search().state.game = game;
+            self.search().state.params.depth = 14;
+            self.stop.store(false, Ordering::Relaxed);
 
-            self.search.go(false);
+            self.search().go(false);
 
-            total += self.search.state.info.nodes;
+            total += self.search().total_nodes();
         }
-        let elapsed = Local::now().timestamp_millis() - start_time;
+        let elapsed = clock.elapsed();
 
         println!(
             "{} nodes {} nps",
             total,
-            total as u128 * 1000 / (elapsed + 1) as u128
+            total as u128 * 1000 / (elapsed + 1)
         );
     }
 
+    /// Resets the search limits left over from the previous `go` command.
+    fn reset_go_params(&mut self) {
+        self.search().state.tc.time_mode = TimeMode::Infinite;
+        self.search().state.params.depth = u8::MAX;
+        self.search().state.params.ponder = false;
+        self.search().state.params.infinite = false;
+        self.search().state.params.nodes = None;
+        self.search().state.params.mate = None;
+        self.search().state.params.moves_to_go = None;
+        self.search().state.params.w_inc = 0;
+        self.search().state.params.b_inc = 0;
+        self.search().state.params.search_moves.clear();
+    }
+
     /// Handles the 'go' command with various self.search.state.parameters.
     ///
     /// # Arguments
@@ -165,28 +342,39 @@ impl UciController {
                 "depth" => self.handle_go_depth(tokens),
                 "movetime" => self.handle_go_movetime(tokens),
                 "infinite" => self.handle_go_infinite(tokens),
+                "ponder" => self.handle_go_ponder(tokens),
+                "nodes" => self.handle_go_nodes(tokens),
+                "mate" => self.handle_go_mate(tokens),
+                "movestogo" => self.handle_go_movestogo(tokens),
+                "searchmoves" => self.handle_go_searchmoves(tokens),
                 _ => Logger::log(&format!("Unknown go command: {}", token.unwrap())),
             },
-            false => {
-                self.search.go(true);
-            }
+            false => self.start_search(),
         }
     }
 
+    /// Sets up the increment per move for white.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing increment value in milliseconds
     fn handle_winc(&mut self, tokens: &mut Queue<&str>) {
         let token = tokens.remove().unwrap();
-        let _inc = token.parse::<u32>().unwrap();
+        let inc = token.parse::<u128>().unwrap();
 
-        // TODO: save winc
+        self.search().state.params.w_inc = inc;
 
         self.handle_go(tokens);
     }
 
+    /// Sets up the increment per move for black.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing increment value in milliseconds
     fn handle_binc(&mut self, tokens: &mut Queue<&str>) {
         let token = tokens.remove().unwrap();
-        let _inc = token.parse::<u32>().unwrap();
+        let inc = token.parse::<u128>().unwrap();
 
-        // TODO: save binc
+        self.search().state.params.b_inc = inc;
 
         self.handle_go(tokens);
     }
@@ -199,9 +387,9 @@ impl UciController {
         let token = tokens.remove().unwrap();
         let time = token.parse::<u128>().unwrap();
 
-        self.search.state.params.depth = u8::MAX;
-        self.search.state.tc.time_mode = TimeMode::WOrBTime;
-        self.search.state.params.b_time = time;
+        self.search().state.params.depth = u8::MAX;
+        self.search().state.tc.time_mode = TimeMode::WOrBTime;
+        self.search().state.params.b_time = time;
 
         self.handle_go(tokens);
     }
@@ -214,9 +402,9 @@ impl UciController {
         let token = tokens.remove().unwrap();
         let time = token.parse::<u128>().unwrap();
 
-        self.search.state.params.depth = u8::MAX;
-        self.search.state.tc.time_mode = TimeMode::WOrBTime;
-        self.search.state.params.w_time = time;
+        self.search().state.params.depth = u8::MAX;
+        self.search().state.tc.time_mode = TimeMode::WOrBTime;
+        self.search().state.params.w_time = time;
 
         self.handle_go(tokens);
     }
@@ -229,8 +417,8 @@ impl UciController {
         let token = tokens.remove().unwrap();
         let depth = token.parse::<u8>().unwrap();
 
-        self.search.state.params.depth = depth;
-        self.search.state.tc.time_mode = TimeMode::Infinite;
+        self.search().state.params.depth = depth;
+        self.search().state.tc.time_mode = TimeMode::Infinite;
 
         self.handle_go(tokens);
     }
@@ -243,9 +431,72 @@ impl UciController {
         let token = tokens.remove().unwrap();
         let time = token.parse::<u128>().unwrap();
 
-        self.search.state.params.move_time = time;
-        self.search.state.tc.time_mode = TimeMode::MoveTime;
-        self.search.state.params.depth = u8::MAX;
+        self.search().state.params.move_time = time;
+        self.search().state.tc.time_mode = TimeMode::MoveTime;
+        self.search().state.params.depth = u8::MAX;
+
+        self.handle_go(tokens);
+    }
+
+    /// Sets up a search limited to a number of nodes.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing the node limit
+    fn handle_go_nodes(&mut self, tokens: &mut Queue<&str>) {
+        let token = tokens.remove().unwrap();
+        let nodes = token.parse::<u64>().unwrap();
+
+        self.search().state.params.nodes = Some(nodes);
+
+        self.handle_go(tokens);
+    }
+
+    /// Sets up a search for a mate in a given number of moves.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing the number of moves
+    fn handle_go_mate(&mut self, tokens: &mut Queue<&str>) {
+        let token = tokens.remove().unwrap();
+        let moves = token.parse::<u8>().unwrap();
+
+        self.search().state.params.mate = Some(moves);
+
+        self.handle_go(tokens);
+    }
+
+    /// Sets the number of moves left until the next time control.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing the number of moves
+    fn handle_go_movestogo(&mut self, tokens: &mut Queue<&str>) {
+        let token = tokens.remove().unwrap();
+        let moves = token.parse::<u32>().unwrap();
+
+        self.search().state.params.moves_to_go = Some(moves);
+
+        self.handle_go(tokens);
+    }
+
+    /// Restricts the search to the given root moves.
+    /// Consumes tokens as long as they are legal moves in the current position.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue containing the moves in UCI notation
+    fn handle_go_searchmoves(&mut self, tokens: &mut Queue<&str>) {
+        while let Ok(token) = tokens.peek() {
+            let state = &mut self.search().state;
+            let legal = token
+                .parse::<UciMove>()
+                .ok()
+                .and_then(|uci_move| uci_move.to_move(&state.game).ok());
+
+            match legal {
+                Some(m) => state.params.search_moves.push(m),
+                None => break,
+            }
+
+            tokens.remove().unwrap();
+        }
 
         self.handle_go(tokens);
     }
@@ -271,8 +522,8 @@ impl UciController {
     /// # Arguments
     /// * `tokens` - Queue containing moves to apply
     fn handle_position_startpos(&mut self, tokens: &mut Queue<&str>) {
-        self.search.state.game = Chess::default();
-        self.search.state.hstack.clear();
+        self.search().state.game = Chess::default();
+        self.search().state.hstack.clear();
 
         if let Ok(moves) = tokens.remove() {
             if moves != "moves" {
@@ -280,22 +531,20 @@ impl UciController {
             }
 
             while let Ok(move_str) = tokens.remove() {
+                let state = &mut self.search().state;
                 let uci_move = move_str.parse::<UciMove>().ok();
-                let game = self.search.state.game.clone();
+                let game = state.game.clone();
                 let legal = uci_move.unwrap().to_move(&game).ok().unwrap();
 
-                self.search.state.game = game.play(&legal).unwrap();
-                self.search.state.hstack.push(
-                    self.search
-                        .state
-                        .game
-                        .zobrist_hash(shakmaty::EnPassantMode::Legal),
-                    None,
-                )
+                state.game = game.play(&legal).unwrap();
+                state
+                    .hstack
+                    .push(state.game.zobrist_hash(shakmaty::EnPassantMode::Legal))
             }
         }
 
-        self.search.state.nnue = NNUEState::from_board(self.search.state.game.board());
+        let state = &mut self.search().state;
+        state.nnue = NNUEState::from_board(state.game.board());
     }
 
     /// Sets up a position from FEN string and applies moves.
@@ -323,27 +572,25 @@ impl UciController {
 
         let fen: Fen = fen_vec.join(" ").as_str().parse().ok().unwrap();
 
-        self.search.state.game = fen.into_position(CastlingMode::Standard).ok().unwrap();
-        self.search.state.hstack.clear();
+        self.search().state.game = fen.into_position(CastlingMode::Standard).ok().unwrap();
+        self.search().state.hstack.clear();
 
         if token == "moves" {
             while let Ok(move_str) = tokens.remove() {
+                let state = &mut self.search().state;
                 let uci_move = move_str.parse::<UciMove>().ok();
-                let game = self.search.state.game.clone();
+                let game = state.game.clone();
                 let legal = uci_move.unwrap().to_move(&game).ok().unwrap();
 
-                self.search.state.game = game.play(&legal).unwrap();
-                self.search.state.hstack.push(
-                    self.search
-                        .state
-                        .game
-                        .zobrist_hash(shakmaty::EnPassantMode::Legal),
-                    None,
-                )
+                state.game = game.play(&legal).unwrap();
+                state
+                    .hstack
+                    .push(state.game.zobrist_hash(shakmaty::EnPassantMode::Legal))
             }
         }
 
-        self.search.state.nnue = NNUEState::from_board(self.search.state.game.board());
+        let state = &mut self.search().state;
+        state.nnue = NNUEState::from_board(state.game.board());
     }
 
     /// Processes option setting commands.
@@ -351,90 +598,175 @@ impl UciController {
     /// # Arguments
     /// * `tokens` - Queue containing option name and value
     fn handle_setoption(&mut self, tokens: &mut Queue<&str>) {
-        if tokens.size() < 4 {
+        if tokens.size() < 2 {
             return;
         }
 
         tokens.remove().unwrap(); // name
-        let name = tokens.remove().unwrap();
-        tokens.remove().unwrap(); // value
-        let value = tokens.remove().unwrap();
+
+        /* Option names may contain spaces, they run until the value keyword */
+        let mut name_parts = Vec::new();
+        while let Ok(token) = tokens.remove() {
+            if token == "value" {
+                break;
+            }
+            name_parts.push(token);
+        }
+        let name = name_parts.join(" ");
+        let mut value_parts = Vec::new();
+        while let Ok(token) = tokens.remove() {
+            value_parts.push(token);
+        }
+        let value = value_parts.join(" ");
+
+        /* Buttons have no value */
+        if name == self.search().state.cfg.clear_hash.name {
+            self.search().state.tt.clear();
+            return;
+        }
 
         if name.is_empty() || value.is_empty() {
             return;
         }
 
-        match name {
-            "MoveOverhead" => Logger::log("info string MoveOverhead is not yet supported."),
-            "Threads" => Logger::log("info string Multithreading is not yet supported."),
+        match name.as_str() {
+            "HashFile" => self.search().state.cfg.hash_file.value = value,
+            "MoveOverhead" => {
+                self.search().state.cfg.move_overhead.value = value.parse::<usize>().unwrap()
+            }
+            "Ponder" => self.search().state.cfg.ponder.value = value.parse::<bool>().unwrap(),
+            "MultiPV" => self.search().state.cfg.multi_pv.value = value.parse::<usize>().unwrap(),
+            "Threads" => {
+                let threads = value.parse::<u8>().unwrap();
+
+                self.search().state.cfg.threads.value = threads;
+                self.search().set_threads(threads as usize);
+            }
             "Hash" => {
-                let size = value.parse::<u32>().unwrap();
-                let bytes = size * 1024 * 1024;
-                let entries = bytes / 24; // 24 is the actual size of one entry
+                let size = value.parse::<usize>().unwrap();
 
-                self.search.state.tt = TranspositionTable::new(entries as usize);
+                let threads = self.search().state.cfg.threads.value as usize;
+
+                self.search().state.cfg.hash.value = size;
+                self.search().state.tt = Arc::new(TranspositionTable::new(size, threads));
             }
             "QSearchDepth" => {
-                self.search.state.cfg.qsearch_depth.value = value.parse::<u8>().unwrap()
+                self.search().state.cfg.qsearch_depth.value = value.parse::<u8>().unwrap()
             }
-            "RFPDepth" => self.search.state.cfg.rfp_depth.value = value.parse::<u8>().unwrap(),
+            "RFPDepth" => self.search().state.cfg.rfp_depth.value = value.parse::<u8>().unwrap(),
             "RFPBaseMargin" => {
-                self.search.state.cfg.rfp_base_margin.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.rfp_base_margin.value = value.parse::<i32>().unwrap()
             }
             "RFPReductionImproving" => {
-                self.search.state.cfg.rfp_reduction_improving.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.rfp_reduction_improving.value =
+                    value.parse::<i32>().unwrap()
             }
             "FPDepthMargin" => {
-                self.search.state.cfg.fp_depth_margin.value = value.parse::<u8>().unwrap()
+                self.search().state.cfg.fp_depth_margin.value = value.parse::<u8>().unwrap()
             }
             "FPBaseMargin" => {
-                self.search.state.cfg.fp_base_margin.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.fp_base_margin.value = value.parse::<i32>().unwrap()
             }
             "FPMarginDepthFactor" => {
-                self.search.state.cfg.fp_margin_depth_factor.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.fp_margin_depth_factor.value = value.parse::<i32>().unwrap()
+            }
+            "NMPDepth" => self.search().state.cfg.nmp_depth.value = value.parse::<u8>().unwrap(),
+            "NMPMargin" => self.search().state.cfg.nmp_margin.value = value.parse::<u8>().unwrap(),
+            "NMPDivisor" => {
+                self.search().state.cfg.nmp_divisor.value = value.parse::<u8>().unwrap()
             }
-            "NMPDepth" => self.search.state.cfg.nmp_depth.value = value.parse::<u8>().unwrap(),
-            "NMPMargin" => self.search.state.cfg.nmp_margin.value = value.parse::<u8>().unwrap(),
-            "NMPDivisor" => self.search.state.cfg.nmp_divisor.value = value.parse::<u8>().unwrap(),
             "NMPDivisorImproving" => {
-                self.search.state.cfg.nmp_divisor_improving.value = value.parse::<u8>().unwrap()
+                self.search().state.cfg.nmp_divisor_improving.value = value.parse::<u8>().unwrap()
+            }
+            "NMPVerificationDepth" => {
+                self.search().state.cfg.nmp_verification_depth.value = value.parse::<u8>().unwrap()
             }
             "LMPMoveMargin" => {
-                self.search.state.cfg.lmp_move_margin.value = value.parse::<usize>().unwrap()
+                self.search().state.cfg.lmp_move_margin.value = value.parse::<usize>().unwrap()
             }
             "LMPDepthFactor" => {
-                self.search.state.cfg.lmp_depth_factor.value = value.parse::<u8>().unwrap()
+                self.search().state.cfg.lmp_depth_factor.value = value.parse::<u8>().unwrap()
+            }
+            "SEEDepth" => self.search().state.cfg.see_depth.value = value.parse::<u8>().unwrap(),
+            "SEECaptureMargin" => {
+                self.search().state.cfg.see_capture_margin.value = value.parse::<i32>().unwrap()
+            }
+            "SEEQuietMargin" => {
+                self.search().state.cfg.see_quiet_margin.value = value.parse::<i32>().unwrap()
+            }
+            "SEDepth" => self.search().state.cfg.se_depth.value = value.parse::<u8>().unwrap(),
+            "SETTDepthMargin" => {
+                self.search().state.cfg.se_tt_depth_margin.value = value.parse::<u8>().unwrap()
             }
-            "LMRDepth" => self.search.state.cfg.lmr_depth.value = value.parse::<u8>().unwrap(),
+            "SEBetaMargin" => {
+                self.search().state.cfg.se_beta_margin.value = value.parse::<i32>().unwrap()
+            }
+            "SEDoubleMargin" => {
+                self.search().state.cfg.se_double_margin.value = value.parse::<i32>().unwrap()
+            }
+            "SEDoubleLimit" => {
+                self.search().state.cfg.se_double_limit.value = value.parse::<u8>().unwrap()
+            }
+            "LMRDepth" => self.search().state.cfg.lmr_depth.value = value.parse::<u8>().unwrap(),
             "LMRMoveMargin" => {
-                self.search.state.cfg.lmr_move_margin.value = value.parse::<usize>().unwrap()
+                self.search().state.cfg.lmr_move_margin.value = value.parse::<usize>().unwrap()
             }
             "LMRQuietMargin" => {
-                self.search.state.cfg.lmr_quiet_margin.value = value.parse::<f64>().unwrap()
+                self.search().state.cfg.lmr_quiet_margin.value = value.parse::<f64>().unwrap()
             }
             "LMRQuietDivisor" => {
-                self.search.state.cfg.lmr_quiet_divisor.value = value.parse::<f64>().unwrap()
+                self.search().state.cfg.lmr_quiet_divisor.value = value.parse::<f64>().unwrap()
             }
             "LMRBaseMargin" => {
-                self.search.state.cfg.lmr_base_margin.value = value.parse::<f64>().unwrap()
+                self.search().state.cfg.lmr_base_margin.value = value.parse::<f64>().unwrap()
             }
             "LMRBaseDivisor" => {
-                self.search.state.cfg.lmr_base_divisor.value = value.parse::<f64>().unwrap()
+                self.search().state.cfg.lmr_base_divisor.value = value.parse::<f64>().unwrap()
             }
-            "MOTTEntryValue" => {
-                self.search.state.cfg.mo_tt_entry_value.value = value.parse::<i32>().unwrap()
+            "LMRHistoryDivisor" => {
+                self.search().state.cfg.lmr_history_divisor.value = value.parse::<i32>().unwrap()
             }
             "MOCaptureValue" => {
-                self.search.state.cfg.mo_capture_value.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.mo_capture_value.value = value.parse::<i32>().unwrap()
             }
             "MOKillerValue" => {
-                self.search.state.cfg.mo_killer_value.value = value.parse::<i32>().unwrap()
+                self.search().state.cfg.mo_killer_value.value = value.parse::<i32>().unwrap()
             }
+            "ASPDepth" => self.search().state.cfg.asp_depth.value = value.parse::<u8>().unwrap(),
+            "ASPDelta" => self.search().state.cfg.asp_delta.value = value.parse::<i32>().unwrap(),
+            "ASPGrowth" => self.search().state.cfg.asp_growth.value = value.parse::<i32>().unwrap(),
             "TCTimeDivisor" => {
-                self.search.state.cfg.tc_time_divisor.value = value.parse::<u64>().unwrap()
+                self.search().state.cfg.tc_time_divisor.value = value.parse::<u64>().unwrap()
+            }
+            "TCMovesLeftMin" => {
+                self.search().state.cfg.tc_moves_left_min.value = value.parse::<u64>().unwrap()
+            }
+            "TCMovesLeftMax" => {
+                self.search().state.cfg.tc_moves_left_max.value = value.parse::<u64>().unwrap()
+            }
+            "TCIncFactor" => {
+                self.search().state.cfg.tc_inc_factor.value = value.parse::<u64>().unwrap()
+            }
+            "TCHardFactor" => {
+                self.search().state.cfg.tc_hard_factor.value = value.parse::<u64>().unwrap()
+            }
+            "TCStabilityBase" => {
+                self.search().state.cfg.tc_stability_base.value = value.parse::<u64>().unwrap()
+            }
+            "TCStabilityStep" => {
+                self.search().state.cfg.tc_stability_step.value = value.parse::<u64>().unwrap()
+            }
+            "TCScoreDropFactor" => {
+                self.search().state.cfg.tc_score_drop_factor.value = value.parse::<i64>().unwrap()
+            }
+            "TCNodesBase" => {
+                self.search().state.cfg.tc_nodes_base.value = value.parse::<u64>().unwrap()
+            }
+            "TCNodesFactor" => {
+                self.search().state.cfg.tc_nodes_factor.value = value.parse::<u64>().unwrap()
             }
             "TCElapsedFactor" => {
-                self.search.state.cfg.tc_elapsed_factor.value = value.parse::<i64>().unwrap()
+                self.search().state.cfg.tc_elapsed_factor.value = value.parse::<u64>().unwrap()
             }
 
             _ => Logger::log(&format!("info string unknown option: {}", name)),
@@ -446,15 +778,28 @@ impl UciController {
     /// # Arguments
     /// * `tokens` - Queue of remaining tokens to process
     fn handle_go_infinite(&mut self, tokens: &mut Queue<&str>) {
-        self.search.state.params.depth = u8::MAX;
-        self.search.state.tc.time_mode = TimeMode::Infinite;
+        self.search().state.params.depth = u8::MAX;
+        self.search().state.params.infinite = true;
+        self.search().state.tc.time_mode = TimeMode::Infinite;
+
+        self.handle_go(tokens);
+    }
+
+    /// Sets up a search on the opponent's time, the time limits that follow
+    /// only apply once `ponderhit` is received.
+    ///
+    /// # Arguments
+    /// * `tokens` - Queue of remaining tokens to process
+    fn handle_go_ponder(&mut self, tokens: &mut Queue<&str>) {
+        self.search().state.params.ponder = true;
 
         self.handle_go(tokens);
     }
 
-    /// Resets the game to initial position.
+    /// Resets the game to initial position and forgets everything learnt
+    /// during the previous game, so that games are reproducible.
     fn handle_ucinewgame(&mut self) {
-        self.search.state.game = Chess::default();
+        self.search().new_game();
     }
 
     /// Responds to isready command.
@@ -462,45 +807,83 @@ impl UciController {
         Logger::log("readyok");
     }
 
-    /// Handles quit command by exiting the program.
-    fn handle_quit(&self) {
+    /// Interrupts the running search, which then reports its best move.
+    fn handle_stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// The opponent played the expected move, the ponder search goes on with its normal time budget.
+    fn handle_ponderhit(&self) {
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+
+    /// Handles quit command by stopping the search and exiting the program.
+    fn handle_quit(&mut self) {
+        self.handle_stop();
+        self.search();
+
         std::process::exit(0);
     }
 
     /// Sends engine identification and available options.
-    fn handle_uci(&self) {
+    fn handle_uci(&mut self) {
         Logger::log(r#"id name Pluto"#);
         Logger::log(r#"id author CastledChess"#);
 
-        Logger::log(format!("{}", self.search.state.cfg.move_overhead).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.threads).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.hash).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.move_overhead).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.threads).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.hash).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.ponder).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.multi_pv).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.clear_hash).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.hash_file).as_str());
 
         // Values to tune
-        Logger::log(format!("{}", self.search.state.cfg.qsearch_depth).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.rfp_depth).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.rfp_base_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.rfp_reduction_improving).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.fp_base_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.fp_depth_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.fp_margin_depth_factor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.nmp_depth).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.nmp_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.nmp_divisor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.nmp_divisor_improving).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmp_move_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmp_depth_factor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_depth).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_move_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_quiet_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_quiet_divisor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_base_margin).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.lmr_base_divisor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.mo_tt_entry_value).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.mo_capture_value).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.mo_killer_value).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.tc_time_divisor).as_str());
-        Logger::log(format!("{}", self.search.state.cfg.tc_elapsed_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.qsearch_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.rfp_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.rfp_base_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.rfp_reduction_improving).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.fp_base_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.fp_depth_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.fp_margin_depth_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.nmp_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.nmp_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.nmp_divisor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.nmp_divisor_improving).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.nmp_verification_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmp_move_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmp_depth_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.see_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.see_capture_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.see_quiet_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.se_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.se_tt_depth_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.se_beta_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.se_double_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.se_double_limit).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_move_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_quiet_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_quiet_divisor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_base_margin).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_base_divisor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.lmr_history_divisor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.mo_capture_value).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.mo_killer_value).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.asp_depth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.asp_delta).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.asp_growth).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_time_divisor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_moves_left_min).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_moves_left_max).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_inc_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_hard_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_stability_base).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_stability_step).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_score_drop_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_nodes_base).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_nodes_factor).as_str());
+        Logger::log(format!("{}", self.search().state.cfg.tc_elapsed_factor).as_str());
         // Values to tune
 
         Logger::log(r#"uciok"#);